@@ -0,0 +1,616 @@
+use std::{fmt, sync::Arc};
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::{BoxedFuture, HashMap},
+};
+use bevy_pathmesh::PathMesh;
+use serde::{Deserialize, Serialize};
+use spade::{ConstrainedDelaunayTriangulation, Point2, Triangulation};
+
+use crate::{
+    colliders::NavMeshPlane,
+    navmesh_dimensions,
+    pathfinding::NavMeshGraph,
+    pathmesh_trimeshes, update_off_mesh_links,
+    utils::{NavMeshArea, NavMeshRegions},
+    LayerMesh, NavHeightField, NavMeshBounds2d, NavMeshBuildStats, NavMeshGenerated, NavMeshLayer,
+    NavMeshSet, NavMeshSettings, NavMeshStatus, NavMeshTask, PendingFootprints, PolyNavMesh,
+};
+
+const MAGIC: &[u8; 4] = b"PNAV";
+
+/// Bumped whenever the layout of [`BakedNavMesh`] changes.
+pub const BAKE_VERSION: u32 = 3;
+
+/// Loads baked navmeshes and applies them when a [`LoadBakedNavMesh`] is inserted. Set
+/// [`NavMeshSettings::build_on_startup`] to false so a fresh navmesh isn't built alongside it.
+#[derive(Default)]
+pub struct BakedNavMeshPlugin;
+
+impl Plugin for BakedNavMeshPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<BakedNavMesh>()
+            .add_asset::<RestoredLayers>()
+            .init_asset_loader::<BakedNavMeshLoader>()
+            .add_systems(
+                PreUpdate,
+                // The baked navmesh is inserted with commands, which have to be applied for its
+                // links to be resolved this frame.
+                (apply_baked_navmesh, apply_deferred)
+                    .chain()
+                    .in_set(NavMeshSet::Poll)
+                    .before(update_off_mesh_links),
+            );
+    }
+}
+
+/// A generated navmesh with everything needed to keep updating it at runtime. Saved as `.navmesh`
+/// (binary) or `.navmesh.ron`.
+#[derive(Serialize, Deserialize, TypeUuid, TypePath, Clone, Debug, PartialEq)]
+#[uuid = "9f3c2a51-7d4e-4b8a-a0f6-3e1b5c7d9e21"]
+pub struct BakedNavMesh {
+    pub version: u32,
+    /// [`PolyNavMesh::inputs_hash`] at the time of baking.
+    pub inputs_hash: u64,
+    pub plane: NavMeshPlane,
+    pub dimensions: (f32, f32),
+    /// [`PolyNavMesh::origin`].
+    pub origin: (f32, f32),
+    /// [`NavMeshSettings::min_island_area`] the polygons were filtered with.
+    pub min_island_area: f32,
+    pub layers: Vec<BakedLayer>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BakedLayer {
+    pub layer: NavMeshLayer,
+    /// Every vertex of the triangulation.
+    pub vertices: Vec<[f32; 2]>,
+    /// Pairs of indices into `vertices`.
+    pub constraints: Vec<[u32; 2]>,
+    pub obstacles: Vec<Vec<[f32; 2]>>,
+    pub walkable: Vec<Vec<[f32; 2]>>,
+    /// See [`NavMeshRegions::walkable_only`].
+    pub walkable_only: bool,
+    pub areas: Vec<(NavMeshArea, Vec<[f32; 2]>)>,
+    /// The navigable triangles, used to build the [`PathMesh`] without retriangulating.
+    pub polygon_vertices: Vec<[f32; 2]>,
+    pub polygons: Vec<[usize; 3]>,
+}
+
+#[derive(Debug)]
+pub enum BakeError {
+    /// The file doesn't start with the baked navmesh header.
+    NotABakedNavMesh,
+    UnsupportedVersion(u32),
+    Binary(bincode::Error),
+    Ron(ron::Error),
+    /// The navmesh parses, but its vertices, constraints or polygons don't fit together.
+    Invalid(String),
+}
+
+impl fmt::Display for BakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BakeError::NotABakedNavMesh => write!(f, "not a baked navmesh"),
+            BakeError::UnsupportedVersion(version) => write!(
+                f,
+                "baked navmesh version {} is not supported, expected {}",
+                version, BAKE_VERSION
+            ),
+            BakeError::Binary(error) => write!(f, "invalid baked navmesh: {}", error),
+            BakeError::Ron(error) => write!(f, "invalid baked navmesh: {}", error),
+            BakeError::Invalid(error) => write!(f, "invalid baked navmesh: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for BakeError {}
+
+impl BakedNavMesh {
+    pub fn from_navmesh(navmesh: &PolyNavMesh) -> Self {
+        let _span = info_span!("bake_navmesh").entered();
+        Self {
+            version: BAKE_VERSION,
+            inputs_hash: navmesh.inputs_hash,
+            plane: navmesh.plane,
+            dimensions: navmesh.dimensions,
+            origin: navmesh.origin,
+            min_island_area: navmesh.min_island_area,
+            layers: pathmesh_trimeshes(&navmesh.layers, &navmesh.graph, navmesh.min_island_area)
+                .into_iter()
+                .zip(&navmesh.layers)
+                .map(|(trimesh, layer)| BakedLayer::from_layer(layer, trimesh))
+                .collect(),
+        }
+    }
+
+    /// Whether the navmesh would be different if it was generated from inputs with this hash.
+    pub fn is_stale(&self, inputs_hash: u64) -> bool {
+        self.inputs_hash != inputs_hash
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BakeError> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(BAKE_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, self).map_err(BakeError::Binary)?;
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BakeError> {
+        if bytes.len() < 8 || &bytes[..4] != MAGIC {
+            return Err(BakeError::NotABakedNavMesh);
+        }
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version != BAKE_VERSION {
+            return Err(BakeError::UnsupportedVersion(version));
+        }
+        bincode::deserialize(&bytes[8..]).map_err(BakeError::Binary)
+    }
+
+    pub fn to_ron(&self) -> Result<String, BakeError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(BakeError::Ron)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, BakeError> {
+        let baked: Self = ron::from_str(text).map_err(|error| BakeError::Ron(error.code))?;
+        if baked.version != BAKE_VERSION {
+            return Err(BakeError::UnsupportedVersion(baked.version));
+        }
+        Ok(baked)
+    }
+
+    /// Builds a [`PathMesh`] for each layer, lowest first.
+    pub fn pathmeshes(&self) -> Result<Vec<PathMesh>, BakeError> {
+        self.layers.iter().map(BakedLayer::pathmesh).collect()
+    }
+
+    /// Restores the navmesh, using `navmesh_handles` for the layers' [`PathMesh`]es.
+    pub fn into_navmesh(
+        &self,
+        navmesh_handles: Vec<Handle<PathMesh>>,
+    ) -> Result<PolyNavMesh, BakeError> {
+        let meshes = self.pathmeshes()?.iter().map(PathMesh::get).collect();
+        Ok(self.with_restored(self.restore()?, navmesh_handles, meshes))
+    }
+
+    /// Rebuilds the triangulation of each layer and the graph between them.
+    fn restore(&self) -> Result<RestoredLayers, BakeError> {
+        let layers = self
+            .layers
+            .iter()
+            .map(BakedLayer::to_layer)
+            .collect::<Result<Vec<_>, _>>()?;
+        let graph = NavMeshGraph::from_layers(
+            layers
+                .iter()
+                .map(|layer| (&layer.cdt, &layer.navigable_faces)),
+        );
+        Ok(RestoredLayers { layers, graph })
+    }
+
+    fn with_restored(
+        &self,
+        restored: RestoredLayers,
+        navmesh_handles: Vec<Handle<PathMesh>>,
+        meshes: Vec<Arc<polyanya::Mesh>>,
+    ) -> PolyNavMesh {
+        PolyNavMesh {
+            layers: restored.layers,
+            graph: restored.graph,
+            inputs_hash: self.inputs_hash,
+            min_island_area: self.min_island_area,
+            navmesh_handles,
+            meshes,
+            dimensions: self.dimensions,
+            origin: self.origin,
+            plane: self.plane,
+        }
+    }
+
+    fn stats(&self) -> NavMeshBuildStats {
+        NavMeshBuildStats {
+            vertices: self.layers.iter().map(|layer| layer.vertices.len()).sum(),
+            polygons: self.layers.iter().map(|layer| layer.polygons.len()).sum(),
+            constraints: self
+                .layers
+                .iter()
+                .map(|layer| layer.constraints.len())
+                .sum(),
+            ..default()
+        }
+    }
+}
+
+impl BakedLayer {
+    /// `trimesh` is the layer's navigable part, as it went into its [`PathMesh`].
+    fn from_layer(layer: &LayerMesh, trimesh: polyanya::Trimesh) -> Self {
+        let to_array = |point: &Point2<f32>| [point.x, point.y];
+        let to_arrays = |outline: &Vec<Point2<f32>>| -> Vec<[f32; 2]> {
+            outline.iter().map(to_array).collect()
+        };
+
+        let mut indices = HashMap::new();
+        let vertices = layer
+            .cdt
+            .vertices()
+            .enumerate()
+            .map(|(i, vertex)| {
+                indices.insert(vertex.index(), i as u32);
+                to_array(&vertex.position())
+            })
+            .collect();
+        let constraints = layer
+            .cdt
+            .undirected_edges()
+            .filter(|edge| edge.is_constraint_edge())
+            .map(|edge| edge.vertices().map(|vertex| indices[&vertex.index()]))
+            .collect();
+
+        Self {
+            layer: layer.layer,
+            vertices,
+            constraints,
+            obstacles: layer.regions.obstacles.iter().map(to_arrays).collect(),
+            walkable: layer.regions.walkable.iter().map(to_arrays).collect(),
+            walkable_only: layer.regions.walkable_only,
+            areas: layer
+                .regions
+                .areas
+                .iter()
+                .map(|(area, outline)| (*area, to_arrays(outline)))
+                .collect(),
+            polygon_vertices: trimesh
+                .vertices
+                .iter()
+                .map(|vertex| [vertex.x, vertex.y])
+                .collect(),
+            polygons: trimesh.triangles,
+        }
+    }
+
+    fn to_layer(&self) -> Result<LayerMesh, BakeError> {
+        let to_point = |point: &[f32; 2]| Point2::new(point[0], point[1]);
+        let to_points = |outline: &Vec<[f32; 2]>| -> Vec<Point2<f32>> {
+            outline.iter().map(to_point).collect()
+        };
+
+        let mut cdt = ConstrainedDelaunayTriangulation::<Point2<f32>>::new();
+        let handles = self
+            .vertices
+            .iter()
+            .map(|vertex| {
+                cdt.insert(to_point(vertex)).map_err(|error| {
+                    BakeError::Invalid(format!("vertex {:?}: {:?}", vertex, error))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for &[from, to] in &self.constraints {
+            let (Some(&from), Some(&to)) = (handles.get(from as usize), handles.get(to as usize))
+            else {
+                return Err(BakeError::Invalid(format!(
+                    "constraint {:?} uses a missing vertex",
+                    [from, to]
+                )));
+            };
+            if !cdt.can_add_constraint(from, to) {
+                return Err(BakeError::Invalid(format!(
+                    "constraint from {:?} to {:?} crosses another one",
+                    cdt.vertex(from).position(),
+                    cdt.vertex(to).position()
+                )));
+            }
+            cdt.add_constraint(from, to);
+        }
+
+        let regions = NavMeshRegions {
+            obstacles: self.obstacles.iter().map(to_points).collect(),
+            walkable: self.walkable.iter().map(to_points).collect(),
+            areas: self
+                .areas
+                .iter()
+                .map(|(area, outline)| (*area, to_points(outline)))
+                .collect(),
+            walkable_only: self.walkable_only,
+        };
+        Ok(LayerMesh::new(cdt, regions, self.layer))
+    }
+
+    fn pathmesh(&self) -> Result<PathMesh, BakeError> {
+        if let Some(vertex) = self
+            .polygon_vertices
+            .iter()
+            .find(|vertex| !vertex.iter().all(|value| value.is_finite()))
+        {
+            return Err(BakeError::Invalid(format!(
+                "polygon vertex {:?} isn't finite",
+                vertex
+            )));
+        }
+        if let Some(polygon) = self.polygons.iter().find(|polygon| {
+            polygon
+                .iter()
+                .any(|&vertex| vertex >= self.polygon_vertices.len())
+        }) {
+            return Err(BakeError::Invalid(format!(
+                "polygon {:?} uses a missing vertex",
+                polygon
+            )));
+        }
+
+        let trimesh = polyanya::Trimesh {
+            vertices: self
+                .polygon_vertices
+                .iter()
+                .map(|vertex| Vec2::new(vertex[0], vertex[1]))
+                .collect(),
+            triangles: self.polygons.clone(),
+        };
+        Ok(PathMesh::from_polyanya_mesh(trimesh.into()))
+    }
+}
+
+/// The layers and graph of a loaded [`BakedNavMesh`], restored by the loader so it doesn't happen
+/// on the main thread. Kept as its `layers` labeled asset.
+#[derive(TypeUuid, TypePath)]
+#[uuid = "4b8e6f0d-2c91-4a57-9e3d-8f1a7c5b2d64"]
+struct RestoredLayers {
+    layers: Vec<LayerMesh>,
+    graph: NavMeshGraph,
+}
+
+/// Loads `.navmesh` and `.navmesh.ron` files as [`BakedNavMesh`]es, with each layer's [`PathMesh`]
+/// as a labeled asset named `layer0`, `layer1` and so on. Files whose layers can't be restored fail
+/// to load.
+#[derive(Default)]
+pub struct BakedNavMeshLoader;
+
+impl AssetLoader for BakedNavMeshLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let is_ron = load_context
+                .path()
+                .to_str()
+                .map_or(false, |path| path.ends_with(".ron"));
+            let baked = if is_ron {
+                BakedNavMesh::from_ron(std::str::from_utf8(bytes)?)?
+            } else {
+                BakedNavMesh::from_bytes(bytes)?
+            };
+            let restored = baked.restore()?;
+            for (i, pathmesh) in baked.pathmeshes()?.into_iter().enumerate() {
+                load_context.set_labeled_asset(&format!("layer{}", i), LoadedAsset::new(pathmesh));
+            }
+            load_context.set_labeled_asset("layers", LoadedAsset::new(restored));
+            load_context.set_default_asset(LoadedAsset::new(baked));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["navmesh", "navmesh.ron"]
+    }
+}
+
+/// Replaces the navmesh with a baked one once it has loaded. Affectors that were added before then
+/// are assumed to be part of the bake. If they don't match it, the navmesh is rebuilt instead.
+#[derive(Resource)]
+pub struct LoadBakedNavMesh(pub Handle<BakedNavMesh>);
+
+#[allow(clippy::too_many_arguments)]
+fn apply_baked_navmesh(
+    mut commands: Commands,
+    load: Option<Res<LoadBakedNavMesh>>,
+    asset_server: Res<AssetServer>,
+    baked_navmeshes: Res<Assets<BakedNavMesh>>,
+    mut restored_layers: ResMut<Assets<RestoredLayers>>,
+    mut pathmeshes: ResMut<Assets<PathMesh>>,
+    navmesh: Option<Res<PolyNavMesh>>,
+    (plane, settings, heightfield, bounds_2d): (
+        Res<NavMeshPlane>,
+        Res<NavMeshSettings>,
+        Option<Res<NavHeightField>>,
+        Option<Res<NavMeshBounds2d>>,
+    ),
+    task: Res<NavMeshTask>,
+    mut pending: ResMut<PendingFootprints>,
+    mut status: ResMut<NavMeshStatus>,
+    mut generated_events: EventWriter<NavMeshGenerated>,
+) {
+    let Some(load) = load else {
+        return;
+    };
+    if asset_server.get_load_state(&load.0) == LoadState::Failed {
+        warn!("Failed to load the baked navmesh, rebuilding it instead.");
+        commands.remove_resource::<LoadBakedNavMesh>();
+        pending.rebuild_requested = true;
+        return;
+    }
+    let Some(baked) = baked_navmeshes.get(&load.0) else {
+        return;
+    };
+    if task.0.is_some() {
+        return;
+    }
+    commands.remove_resource::<LoadBakedNavMesh>();
+
+    let dimensions = navmesh_dimensions(*plane, heightfield.as_deref(), bounds_2d.as_deref());
+    let inputs_hash = pending
+        .footprints
+        .iter()
+        .map(|footprint| footprint.inputs_hash())
+        .fold(
+            dimensions.map_or(0, |dimensions| {
+                settings.inputs_hash(dimensions, *plane, heightfield.as_deref())
+            }),
+            u64::wrapping_add,
+        );
+    if baked.plane != *plane || baked.is_stale(inputs_hash) {
+        warn!("The baked navmesh is out of date, rebuilding it instead.");
+        pending.rebuild_requested = true;
+        return;
+    }
+
+    let baked_navmesh = match asset_server.get_handle_path(&load.0) {
+        // The loader has already restored the layers and built each layer's PathMesh as labeled
+        // assets.
+        Some(path) => {
+            let handle = |label: String| AssetPath::new(path.path().to_path_buf(), Some(label));
+            let navmesh_handles: Vec<Handle<PathMesh>> = (0..baked.layers.len())
+                .map(|i| asset_server.get_handle(handle(format!("layer{}", i))))
+                .collect();
+            let meshes: Option<Vec<_>> = navmesh_handles
+                .iter()
+                .map(|handle| pathmeshes.get(handle).map(PathMesh::get))
+                .collect();
+            // Taken out so the triangulations don't have to be cloned. Applying the same file again
+            // restores them from the baked layers.
+            let layers = restored_layers
+                .remove(&asset_server.get_handle::<RestoredLayers, _>(handle("layers".into())));
+            match (layers, meshes) {
+                (Some(layers), Some(meshes)) => {
+                    Ok(baked.with_restored(layers, navmesh_handles, meshes))
+                }
+                _ => baked.into_navmesh(navmesh_handles),
+            }
+        }
+        // Added to the assets directly instead of being loaded.
+        None => baked.restore().and_then(|layers| {
+            let layer_pathmeshes = baked.pathmeshes()?;
+            let meshes = layer_pathmeshes.iter().map(PathMesh::get).collect();
+            let navmesh_handles = layer_pathmeshes
+                .into_iter()
+                .map(|pathmesh| pathmeshes.add(pathmesh))
+                .collect();
+            Ok(baked.with_restored(layers, navmesh_handles, meshes))
+        }),
+    };
+    let baked_navmesh = match baked_navmesh {
+        Ok(baked_navmesh) => baked_navmesh,
+        Err(error) => {
+            warn!("{}, rebuilding the navmesh instead.", error);
+            pending.rebuild_requested = true;
+            return;
+        }
+    };
+    let navmesh_handles = baked_navmesh.navmesh_handles.clone();
+    if let Some(navmesh) = navmesh {
+        for previous_handle in &navmesh.navmesh_handles {
+            if !navmesh_handles.contains(previous_handle) {
+                pathmeshes.remove(previous_handle);
+            }
+        }
+    }
+    commands.insert_resource(baked_navmesh);
+    pending.footprints.clear();
+    pending.frames_waited = 0;
+    *status = NavMeshStatus::Done;
+    generated_events.send(NavMeshGenerated {
+        navmesh_handles,
+        stats: baked.stats(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{colliders::Footprint, testing, utils::AffectorKind};
+
+    fn baked() -> BakedNavMesh {
+        let obstacle = Footprint::Closed(vec![
+            Point2::new(-1.0, -1.0),
+            Point2::new(1.0, -1.0),
+            Point2::new(0.0, 1.0),
+        ]);
+        let layer = testing::layer((10.0, 10.0), &[(AffectorKind::Obstacle, obstacle)]);
+        let mut navmesh = testing::navmesh((10.0, 10.0), vec![layer]);
+        navmesh.inputs_hash = 0x1234_5678_9abc_def0;
+        BakedNavMesh::from_navmesh(&navmesh)
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let baked = baked();
+        assert!(!baked.layers[0].polygons.is_empty());
+        let bytes = baked.to_bytes().unwrap();
+        assert_eq!(BakedNavMesh::from_bytes(&bytes).unwrap(), baked);
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let baked = baked();
+        let text = baked.to_ron().unwrap();
+        assert_eq!(BakedNavMesh::from_ron(&text).unwrap(), baked);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut bytes = baked().to_bytes().unwrap();
+        bytes[4..8].copy_from_slice(&(BAKE_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            BakedNavMesh::from_bytes(&bytes),
+            Err(BakeError::UnsupportedVersion(version)) if version == BAKE_VERSION + 1
+        ));
+        assert!(matches!(
+            BakedNavMesh::from_bytes(b"not a navmesh"),
+            Err(BakeError::NotABakedNavMesh)
+        ));
+
+        let mut old = baked();
+        old.version = BAKE_VERSION - 1;
+        let text = old.to_ron().unwrap();
+        assert!(matches!(
+            BakedNavMesh::from_ron(&text),
+            Err(BakeError::UnsupportedVersion(version)) if version == BAKE_VERSION - 1
+        ));
+    }
+
+    #[test]
+    fn baked_layers_are_restored() {
+        let baked = baked();
+        let navmesh = baked.into_navmesh(Vec::new()).unwrap();
+        assert_eq!(navmesh.layers.len(), 1);
+        assert_eq!(
+            navmesh.layers[0].cdt.num_vertices(),
+            baked.layers[0].vertices.len()
+        );
+        assert_eq!(navmesh.meshes.len(), 1);
+        assert_eq!(navmesh.origin, baked.origin);
+    }
+
+    #[test]
+    fn broken_layers_are_rejected() {
+        let invalid = |change: fn(&mut BakedLayer)| {
+            let mut baked = baked();
+            change(&mut baked.layers[0]);
+            matches!(baked.into_navmesh(Vec::new()), Err(BakeError::Invalid(_)))
+        };
+        assert!(invalid(|layer| layer.vertices[0] = [f32::NAN, 0.0]));
+        assert!(invalid(|layer| layer.vertices[0] = [f32::INFINITY, 0.0]));
+        assert!(invalid(|layer| layer.constraints[0][1] = 1000));
+        assert!(invalid(|layer| {
+            // Both diagonals of the bounding square.
+            let corners: Vec<u32> = [[-5.0, -5.0], [5.0, 5.0], [-5.0, 5.0], [5.0, -5.0]]
+                .iter()
+                .map(|corner| {
+                    layer
+                        .vertices
+                        .iter()
+                        .position(|vertex| vertex == corner)
+                        .unwrap() as u32
+                })
+                .collect();
+            layer.constraints.push([corners[0], corners[1]]);
+            layer.constraints.push([corners[2], corners[3]]);
+        }));
+        assert!(invalid(|layer| layer.polygons[0][2] = 1000));
+        assert!(invalid(|layer| layer.polygon_vertices[0] = [f32::NAN, 0.0]));
+    }
+}
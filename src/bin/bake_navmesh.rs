@@ -0,0 +1,506 @@
+//! Bakes a navmesh without opening a window, for pre-baking levels in a build pipeline.
+//!
+//! ```text
+//! bake_navmesh <scene.ron | scene.json | level.gltf | level.glb> <output.navmesh | output.navmesh.ron> [--size <x> <z>]
+//! ```
+//!
+//! Scene descriptions list a heightfield and obstacles, and can pull in a glTF. In a glTF, nodes
+//! whose extras contain `"navmesh_affector": true` become affectors. Add `"navmesh_walkable": true`
+//! or `"navmesh_area": { "id": 1, "cost": 2.0 }` to make them walkable ground or areas instead.
+//! A glTF on its own gets a flat heightfield of `--size`.
+//!
+//! A summary of the build is printed and written next to the output as `<output>.stats.txt`.
+
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    gltf::GltfExtras,
+    prelude::*,
+    render::{settings::WgpuSettings, RenderPlugin},
+    scene::{SceneInstance, SceneSpawner},
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+use bevy_pathmesh::PathMeshPlugin;
+use bevy_poly_navmesh::{
+    bake::BakedNavMesh,
+    colliders::{
+        mesh::{MeshAffector, MeshCollider, MeshColliderPlugin},
+        parry3d_collider::Parry3dCollider,
+    },
+    obstacle::{NavObstacle, NavObstaclePlugin},
+    utils::{NavMeshAffector, NavMeshArea, NavMeshWalkable},
+    NavHeightField, NavMeshBuildStats, NavMeshColliderPlugin, NavMeshGenerated, NavMeshLayer,
+    NavMeshSettings, PolyNavMesh, PolyanyaNavMeshPlugin, RebuildNavMesh,
+};
+use parry3d::{
+    na::{DMatrix, Vector3},
+    shape::{HeightField, SharedShape},
+};
+use serde::Deserialize;
+
+/// Gives up if the scene hasn't loaded and built by then.
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Deserialize)]
+struct BakeScene {
+    heightfield: HeightFieldDescription,
+    #[serde(default)]
+    settings: SettingsDescription,
+    #[serde(default)]
+    obstacles: Vec<ObstacleDescription>,
+    /// Relative to the scene file.
+    #[serde(default)]
+    gltf: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct HeightFieldDescription {
+    /// Extent along X and Z, centred on the origin.
+    size: [f32; 2],
+    /// Rows along Z of heights along X, scaled by `height_scale`. Flat if empty.
+    #[serde(default)]
+    heights: Vec<Vec<f32>>,
+    #[serde(default = "one")]
+    height_scale: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct SettingsDescription {
+    wall_thickness: f32,
+    agent_radius: f32,
+    agent_height: f32,
+    /// `[floor, ceiling]` pairs, lowest first. A single layer covering every height if empty.
+    layers: Vec<[f32; 2]>,
+}
+
+impl Default for SettingsDescription {
+    fn default() -> Self {
+        let settings = NavMeshSettings::default();
+        Self {
+            wall_thickness: settings.wall_thickness,
+            agent_radius: settings.agent_radius,
+            agent_height: settings.agent_height,
+            layers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ObstacleDescription {
+    #[serde(default)]
+    translation: [f32; 3],
+    /// Rotation around Y, in degrees.
+    #[serde(default)]
+    rotation: f32,
+    shape: ShapeDescription,
+    /// How far `Closed`, `Open` and `Circle` obstacles reach up. They block every layer without it.
+    #[serde(default)]
+    height: Option<f32>,
+    #[serde(default)]
+    walkable: bool,
+    #[serde(default)]
+    area: Option<NavMeshArea>,
+}
+
+#[derive(Deserialize)]
+enum ShapeDescription {
+    Closed { vertices: Vec<[f32; 2]> },
+    Open { vertices: Vec<[f32; 2]> },
+    Circle { radius: f32 },
+    Cuboid { half_extents: [f32; 3] },
+    Cylinder { half_height: f32, radius: f32 },
+    Ball { radius: f32 },
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+struct Arguments {
+    input: PathBuf,
+    output: PathBuf,
+    size: Option<[f32; 2]>,
+}
+
+fn parse_arguments() -> Result<Arguments, String> {
+    let mut args = std::env::args().skip(1);
+    let mut paths = Vec::new();
+    let mut size = None;
+    while let Some(arg) = args.next() {
+        if arg == "--size" {
+            let mut next = || -> Result<f32, String> {
+                args.next()
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| "--size needs two numbers".to_string())
+            };
+            size = Some([next()?, next()?]);
+        } else {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+    let [input, output]: [PathBuf; 2] = paths.try_into().map_err(|_| {
+        "usage: bake_navmesh <scene.ron | scene.json | level.gltf | level.glb> <output.navmesh | output.navmesh.ron> [--size <x> <z>]"
+            .to_string()
+    })?;
+    Ok(Arguments {
+        input,
+        output,
+        size,
+    })
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map_or(false, |extension| {
+            extensions
+                .iter()
+                .any(|candidate| extension.eq_ignore_ascii_case(candidate))
+        })
+}
+
+fn load_scene(arguments: &Arguments) -> Result<BakeScene, String> {
+    let input = &arguments.input;
+    if has_extension(input, &["gltf", "glb"]) {
+        let size = arguments
+            .size
+            .ok_or("--size is needed to bake a glTF without a scene description")?;
+        return Ok(BakeScene {
+            heightfield: HeightFieldDescription {
+                size,
+                heights: Vec::new(),
+                height_scale: 1.0,
+            },
+            settings: SettingsDescription::default(),
+            obstacles: Vec::new(),
+            gltf: input
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+        });
+    }
+
+    let text = std::fs::read_to_string(input)
+        .map_err(|error| format!("failed to read {}: {}", input.display(), error))?;
+    let scene = if has_extension(input, &["json"]) {
+        serde_json::from_str(&text).map_err(|error| error.to_string())
+    } else {
+        ron::from_str(&text).map_err(|error| error.to_string())
+    };
+    scene.map_err(|error| format!("invalid scene {}: {}", input.display(), error))
+}
+
+fn main() -> ExitCode {
+    match bake() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn bake() -> Result<(), String> {
+    let arguments = parse_arguments()?;
+    let scene = load_scene(&arguments)?;
+    let heightfield = heightfield(&scene.heightfield)?;
+    let asset_folder = arguments
+        .input
+        .canonicalize()
+        .ok()
+        .and_then(|input| input.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+
+    let settings = NavMeshSettings {
+        wall_thickness: scene.settings.wall_thickness,
+        agent_radius: scene.settings.agent_radius,
+        agent_height: scene.settings.agent_height,
+        // The first build waits for the scene to finish loading.
+        build_on_startup: false,
+        layers: if scene.settings.layers.is_empty() {
+            vec![NavMeshLayer::default()]
+        } else {
+            scene
+                .settings
+                .layers
+                .iter()
+                .map(|&[floor, ceiling]| NavMeshLayer { floor, ceiling })
+                .collect()
+        },
+        ..default()
+    };
+
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins
+            .set(AssetPlugin {
+                asset_folder: asset_folder.to_string_lossy().into_owned(),
+                ..default()
+            })
+            .set(RenderPlugin {
+                wgpu_settings: WgpuSettings {
+                    backends: None,
+                    ..default()
+                },
+            })
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..default()
+            })
+            .disable::<WinitPlugin>(),
+        PathMeshPlugin,
+        PolyanyaNavMeshPlugin::<Parry3dCollider>::new(settings),
+        NavMeshColliderPlugin::<MeshCollider>::default(),
+        MeshColliderPlugin,
+        NavObstaclePlugin,
+    ))
+    .add_systems(Update, mark_gltf_affectors);
+
+    app.world.insert_resource(NavHeightField {
+        heightfield: Arc::new(heightfield),
+    });
+    for obstacle in &scene.obstacles {
+        spawn_obstacle(&mut app.world, obstacle);
+    }
+    if let Some(gltf) = &scene.gltf {
+        let handle = app
+            .world
+            .resource::<AssetServer>()
+            .load(format!("{}#Scene0", gltf));
+        app.world.spawn(SceneBundle {
+            scene: handle,
+            ..default()
+        });
+    }
+
+    while !app.ready() {
+        bevy::tasks::tick_global_task_pools_on_main_thread();
+    }
+    app.finish();
+    app.cleanup();
+
+    let started = Instant::now();
+    let mut ready_frames = 0;
+    let mut rebuild_sent = false;
+    let stats = loop {
+        if started.elapsed() > TIMEOUT {
+            return Err("timed out waiting for the navmesh to build".to_string());
+        }
+        app.update();
+
+        if !rebuild_sent {
+            // glTF affectors are marked the frame after their scene spawns, so wait for the scene
+            // to stay ready over two frames. Transforms have been propagated by then, so the next
+            // frame collects every affector.
+            ready_frames = if scene_is_ready(&mut app.world) {
+                ready_frames + 1
+            } else {
+                0
+            };
+            if ready_frames >= 2 {
+                app.world.send_event(RebuildNavMesh);
+                rebuild_sent = true;
+            }
+            continue;
+        }
+        if let Some(generated) = app
+            .world
+            .resource_mut::<Events<NavMeshGenerated>>()
+            .drain()
+            .last()
+        {
+            break generated.stats;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    };
+
+    let baked = BakedNavMesh::from_navmesh(app.world.resource::<PolyNavMesh>());
+    let bytes = if arguments.output.to_string_lossy().ends_with(".ron") {
+        baked.to_ron().map(String::into_bytes)
+    } else {
+        baked.to_bytes()
+    }
+    .map_err(|error| error.to_string())?;
+    std::fs::write(&arguments.output, &bytes)
+        .map_err(|error| format!("failed to write {}: {}", arguments.output.display(), error))?;
+
+    let summary = summary(&baked, &stats, bytes.len());
+    print!("{}", summary);
+    let mut stats_path = arguments.output.into_os_string();
+    stats_path.push(".stats.txt");
+    std::fs::write(&stats_path, summary).map_err(|error| {
+        format!(
+            "failed to write {}: {}",
+            Path::new(&stats_path).display(),
+            error
+        )
+    })
+}
+
+fn heightfield(description: &HeightFieldDescription) -> Result<HeightField, String> {
+    let [x, z] = description.size;
+    let heights = if description.heights.is_empty() {
+        DMatrix::zeros(2, 2)
+    } else {
+        let rows = description.heights.len();
+        let columns = description.heights[0].len();
+        // Parry needs at least a 2x2 grid to make a heightfield.
+        if rows < 2 || columns < 2 {
+            return Err(format!(
+                "the heightfield has {} rows of {} heights, it needs at least 2 of 2",
+                rows, columns
+            ));
+        }
+        if let Some(row) = description
+            .heights
+            .iter()
+            .position(|row| row.len() != columns)
+        {
+            return Err(format!(
+                "heightfield row {} has {} heights, but the first row has {}",
+                row,
+                description.heights[row].len(),
+                columns
+            ));
+        }
+        DMatrix::from_fn(rows, columns, |row, column| {
+            description.heights[row][column]
+        })
+    };
+    Ok(HeightField::new(
+        heights,
+        Vector3::new(x, description.height_scale, z),
+    ))
+}
+
+fn spawn_obstacle(world: &mut World, obstacle: &ObstacleDescription) {
+    let [x, y, z] = obstacle.translation;
+    let transform = Transform::from_xyz(x, y, z)
+        .with_rotation(Quat::from_rotation_y(obstacle.rotation.to_radians()));
+    let to_vec2 = |vertices: &Vec<[f32; 2]>| -> Vec<Vec2> {
+        vertices.iter().copied().map(Vec2::from).collect()
+    };
+
+    let mut entity = world.spawn((TransformBundle::from_transform(transform), NavMeshAffector));
+    match &obstacle.shape {
+        ShapeDescription::Closed { vertices } => entity.insert(NavObstacle::Closed {
+            vertices: to_vec2(vertices),
+            height: obstacle.height,
+        }),
+        ShapeDescription::Open { vertices } => entity.insert(NavObstacle::Open {
+            vertices: to_vec2(vertices),
+            height: obstacle.height,
+        }),
+        ShapeDescription::Circle { radius } => entity.insert(NavObstacle::Circle {
+            radius: *radius,
+            height: obstacle.height,
+        }),
+        ShapeDescription::Cuboid {
+            half_extents: [x, y, z],
+        } => entity.insert(Parry3dCollider {
+            collider: SharedShape::cuboid(*x, *y, *z),
+        }),
+        ShapeDescription::Cylinder {
+            half_height,
+            radius,
+        } => entity.insert(Parry3dCollider {
+            collider: SharedShape::cylinder(*half_height, *radius),
+        }),
+        ShapeDescription::Ball { radius } => entity.insert(Parry3dCollider {
+            collider: SharedShape::ball(*radius),
+        }),
+    };
+    if obstacle.walkable {
+        entity.insert(NavMeshWalkable);
+    }
+    if let Some(area) = obstacle.area {
+        entity.insert(area);
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct AffectorExtras {
+    navmesh_affector: bool,
+    navmesh_walkable: bool,
+    navmesh_area: Option<NavMeshArea>,
+}
+
+/// Turns the meshes under glTF nodes marked in their extras into affectors.
+fn mark_gltf_affectors(
+    mut commands: Commands,
+    extras_query: Query<(Entity, &GltfExtras), Added<GltfExtras>>,
+    children_query: Query<&Children>,
+    mesh_query: Query<(), With<Handle<Mesh>>>,
+) {
+    for (entity, extras) in extras_query.iter() {
+        let extras: AffectorExtras = match serde_json::from_str(&extras.value) {
+            Ok(extras) => extras,
+            Err(error) => {
+                warn!("Ignoring invalid glTF extras on {entity:?}: {error}");
+                continue;
+            }
+        };
+        if !extras.navmesh_affector {
+            continue;
+        }
+        for mesh_entity in std::iter::once(entity).chain(children_query.iter_descendants(entity)) {
+            if !mesh_query.contains(mesh_entity) {
+                continue;
+            }
+            let mut mesh_entity = commands.entity(mesh_entity);
+            mesh_entity.insert((MeshAffector, NavMeshAffector));
+            if extras.navmesh_walkable {
+                mesh_entity.insert(NavMeshWalkable);
+            }
+            if let Some(area) = extras.navmesh_area {
+                mesh_entity.insert(area);
+            }
+        }
+    }
+}
+
+/// Whether every glTF scene has spawned and every mesh affector has its collider.
+fn scene_is_ready(world: &mut World) -> bool {
+    let mut scenes = world.query_filtered::<Option<&SceneInstance>, With<Handle<Scene>>>();
+    let scene_spawner = world.resource::<SceneSpawner>();
+    let scenes_ready = scenes.iter(world).all(|instance| {
+        instance.map_or(false, |instance| {
+            scene_spawner.instance_is_ready(**instance)
+        })
+    });
+    let mut loading_meshes =
+        world.query_filtered::<(), (With<MeshAffector>, Without<MeshCollider>)>();
+    scenes_ready && loading_meshes.iter(world).next().is_none()
+}
+
+fn summary(baked: &BakedNavMesh, stats: &NavMeshBuildStats, file_size: usize) -> String {
+    let mut summary = format!(
+        "affectors: {}\nlayers: {}\nvertices: {}\npolygons: {}\nconstraints: {}\nbuild time: {:.2}ms\ninputs hash: {:016x}\nfile size: {} bytes\n",
+        stats.affectors,
+        baked.layers.len(),
+        stats.vertices,
+        stats.polygons,
+        stats.constraints,
+        stats.build_time.as_secs_f64() * 1000.0,
+        baked.inputs_hash,
+        file_size,
+    );
+    for (i, layer) in baked.layers.iter().enumerate() {
+        summary.push_str(&format!(
+            "layer {}: {} to {}, {} polygons\n",
+            i,
+            layer.layer.floor,
+            layer.layer.ceiling,
+            layer.polygons.len()
+        ));
+    }
+    summary
+}
@@ -0,0 +1,90 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::VertexAttributeValues, render_resource::PrimitiveTopology},
+};
+use parry3d::{
+    bounding_volume::Aabb,
+    na::Point3,
+    shape::{SharedShape, TypedShape},
+};
+
+use crate::PolyanyaCollider;
+
+/// Marks an entity's `Handle<Mesh>` as its navmesh footprint, for obstacles without a physics
+/// collider. A [`MeshCollider`] is inserted once the mesh asset has loaded.
+#[derive(Component, Default)]
+pub struct MeshAffector;
+
+/// The triangles of a [`MeshAffector`]'s mesh. Concave meshes such as arches and L-shaped counters
+/// only block the parts of them an agent would bump into.
+#[derive(Component)]
+pub struct MeshCollider {
+    pub collider: SharedShape,
+}
+
+impl PolyanyaCollider for MeshCollider {
+    fn into_typed_shape(&self) -> TypedShape {
+        self.collider.as_typed_shape()
+    }
+
+    fn t_compute_local_aabb(&self) -> Aabb {
+        self.collider.compute_local_aabb()
+    }
+}
+
+/// Add alongside `PolyanyaNavMeshPlugin::<MeshCollider>` or `NavMeshColliderPlugin::<MeshCollider>`.
+pub struct MeshColliderPlugin;
+
+impl Plugin for MeshColliderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, insert_mesh_colliders);
+    }
+}
+
+fn insert_mesh_colliders(
+    mut commands: Commands,
+    affector_query: Query<(Entity, &Handle<Mesh>), (With<MeshAffector>, Without<MeshCollider>)>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for (entity, handle) in affector_query.iter() {
+        // Not loaded yet, try again next frame.
+        let Some(mesh) = meshes.get(handle) else {
+            continue;
+        };
+
+        let Some(positions) = mesh
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .and_then(VertexAttributeValues::as_float3)
+        else {
+            warn!("Mesh of navmesh affector {entity:?} has no vertex positions.");
+            commands.entity(entity).remove::<MeshAffector>();
+            continue;
+        };
+
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            warn!("Mesh of navmesh affector {entity:?} is not a triangle list.");
+            commands.entity(entity).remove::<MeshAffector>();
+            continue;
+        }
+
+        let points: Vec<_> = positions
+            .iter()
+            .map(|[x, y, z]| Point3::new(*x, *y, *z))
+            .collect();
+        let indices: Vec<u32> = match mesh.indices() {
+            Some(indices) => indices.iter().map(|index| index as u32).collect(),
+            None => (0..points.len() as u32).collect(),
+        };
+        let triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect();
+        if triangles.is_empty() {
+            warn!("Mesh of navmesh affector {entity:?} has no triangles.");
+            commands.entity(entity).remove::<MeshAffector>();
+            continue;
+        }
+        let collider = SharedShape::trimesh(points, triangles);
+        commands.entity(entity).insert(MeshCollider { collider });
+    }
+}
@@ -1,14 +1,433 @@
-use bevy::prelude::Component;
+use std::marker::PhantomData;
+
+use bevy::{
+    prelude::{Component, GlobalTransform, Resource, Vec2, Vec3},
+    utils::HashMap,
+};
 use parry3d::{bounding_volume::Aabb, shape::TypedShape};
+use spade::Point2;
+
+use crate::utils::convex_hull_2d;
 
+pub mod mesh;
+#[cfg(feature = "2d")]
+pub mod parry2d_collider;
+pub mod parry3d_collider;
 #[cfg(feature = "rapier")]
 pub mod rapier;
+#[cfg(feature = "rapier2d")]
+pub mod rapier2d;
 #[cfg(feature = "xpbd")]
 pub mod xpbd;
-pub mod parry3d_collider;
+#[cfg(feature = "xpbd2d")]
+pub mod xpbd2d;
 
 pub trait PolyanyaCollider: Component {
     fn into_typed_shape(&self) -> TypedShape;
 
     fn t_compute_local_aabb(&self) -> Aabb;
 }
+
+/// A 2D collider whose shape already lies on the XY plane.
+#[cfg(feature = "2d")]
+pub trait PolyanyaCollider2d: Component {
+    fn into_typed_shape(&self) -> parry2d::shape::TypedShape;
+
+    fn t_compute_local_aabb(&self) -> parry2d::bounding_volume::Aabb;
+}
+
+/// The world plane the navmesh lies on.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum NavMeshPlane {
+    #[default]
+    Xz,
+    Xy,
+}
+
+impl NavMeshPlane {
+    /// Converts a point on the navmesh into world space, offset by `height` along the plane's normal.
+    pub fn to_world(self, point: Vec2, height: f32) -> Vec3 {
+        match self {
+            NavMeshPlane::Xz => Vec3::new(point.x, height, point.y),
+            NavMeshPlane::Xy => Vec3::new(point.x, point.y, height),
+        }
+    }
+
+    /// Projects a world space point onto the plane.
+    pub fn to_plane(self, point: Vec3) -> Vec2 {
+        match self {
+            NavMeshPlane::Xz => Vec2::new(point.x, point.z),
+            NavMeshPlane::Xy => Vec2::new(point.x, point.y),
+        }
+    }
+
+    /// How far a world space point is along the plane's normal.
+    pub fn height(self, point: Vec3) -> f32 {
+        match self {
+            NavMeshPlane::Xz => point.y,
+            NavMeshPlane::Xy => point.z,
+        }
+    }
+}
+
+/// The outline of an affector on the navmesh plane.
+#[derive(Clone, Debug)]
+pub enum Footprint {
+    /// Blocks the area enclosed by the points.
+    Closed(Vec<Point2<f32>>),
+    /// A wall along the points, thickened by [`NavMeshSettings::wall_thickness`](crate::NavMeshSettings::wall_thickness)
+    /// and [`NavMeshSettings::agent_radius`](crate::NavMeshSettings::agent_radius).
+    Open(Vec<Point2<f32>>),
+    /// Blocks the cross-section of a convex shape between the ground and the agent's height.
+    Solid(ConvexSolid),
+    /// Like [`Footprint::Solid`], for a closed triangle mesh that may be concave.
+    Mesh(SolidMesh),
+}
+
+/// A convex shape with its vertices given as a point on the navmesh plane and a height along the
+/// plane's normal.
+#[derive(Clone, Debug)]
+pub struct ConvexSolid {
+    pub vertices: Vec<(Point2<f32>, f32)>,
+    pub edges: Vec<[u32; 2]>,
+}
+
+impl ConvexSolid {
+    /// Builds the solid from world space vertices and the triangles between them.
+    pub fn from_trimesh(vertices: &[Vec3], triangles: &[[u32; 3]], plane: NavMeshPlane) -> Self {
+        Self {
+            vertices: vertices
+                .iter()
+                .map(|vertex| {
+                    let point = plane.to_plane(*vertex);
+                    (Point2::new(point.x, point.y), plane.height(*vertex))
+                })
+                .collect(),
+            edges: triangles
+                .iter()
+                .flat_map(|&[a, b, c]| [[a, b], [b, c], [c, a]])
+                .collect(),
+        }
+    }
+
+    /// The outline of the part of the solid between `bottom` and `top`, projected onto the plane.
+    /// Empty if the solid doesn't reach into that range.
+    pub fn slice(&self, bottom: f32, top: f32) -> Vec<Point2<f32>> {
+        let mut points: Vec<Point2<f32>> = self
+            .vertices
+            .iter()
+            .filter(|(_, height)| (bottom..=top).contains(height))
+            .map(|(point, _)| *point)
+            .collect();
+        for &[a, b] in &self.edges {
+            let (a, a_height) = self.vertices[a as usize];
+            let (b, b_height) = self.vertices[b as usize];
+            for height in [bottom, top] {
+                if (a_height - height) * (b_height - height) < 0.0 {
+                    let t = (height - a_height) / (b_height - a_height);
+                    points.push(Point2::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t));
+                }
+            }
+        }
+
+        let hull = convex_hull_2d(points);
+        if hull.len() < 3 {
+            return Vec::new();
+        }
+        hull
+    }
+}
+
+/// A triangle mesh with its vertices given like [`ConvexSolid`]'s.
+#[derive(Clone, Debug)]
+pub struct SolidMesh {
+    pub vertices: Vec<(Point2<f32>, f32)>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl SolidMesh {
+    pub fn from_trimesh(vertices: &[Vec3], triangles: &[[u32; 3]], plane: NavMeshPlane) -> Self {
+        let solid = ConvexSolid::from_trimesh(vertices, &[], plane);
+        Self {
+            vertices: solid.vertices,
+            triangles: triangles.to_vec(),
+        }
+    }
+
+    /// Outlines that together cover the part of the mesh between `bottom` and `top`, projected onto
+    /// the plane. Each triangle is sliced on its own, and as faces that are vertical within the
+    /// range cover nothing, the cross-section at `bottom` is added for the parts of the mesh that
+    /// pass through it.
+    pub fn slice(&self, bottom: f32, top: f32) -> Vec<Vec<Point2<f32>>> {
+        let mut outlines: Vec<_> = self
+            .triangles
+            .iter()
+            .map(|triangle| {
+                ConvexSolid {
+                    vertices: triangle
+                        .iter()
+                        .map(|&vertex| self.vertices[vertex as usize])
+                        .collect(),
+                    edges: vec![[0, 1], [1, 2], [2, 0]],
+                }
+                .slice(bottom, top)
+            })
+            .filter(|outline| !outline.is_empty())
+            .collect();
+        outlines.extend(self.cross_section(bottom));
+        outlines
+    }
+
+    /// The loops where the surface crosses `height`. Vertices exactly at `height` count as above
+    /// it, so every triangle crosses it along none or two of its edges.
+    fn cross_section(&self, height: f32) -> Vec<Vec<Point2<f32>>> {
+        let above = |vertex: u32| self.vertices[vertex as usize].1 >= height;
+        // Crossings are keyed by the edge they're on, which both triangles sharing it agree on.
+        let mut neighbours: HashMap<[u32; 2], Vec<[u32; 2]>> = HashMap::new();
+        for &[a, b, c] in &self.triangles {
+            let crossings: Vec<[u32; 2]> = [[a, b], [b, c], [c, a]]
+                .into_iter()
+                .filter(|&[from, to]| above(from) != above(to))
+                .map(|[from, to]| [from.min(to), from.max(to)])
+                .collect();
+            if let [first, second] = crossings[..] {
+                neighbours.entry(first).or_default().push(second);
+                neighbours.entry(second).or_default().push(first);
+            }
+        }
+
+        let crossing_point = |[a, b]: [u32; 2]| {
+            let (a, a_height) = self.vertices[a as usize];
+            let (b, b_height) = self.vertices[b as usize];
+            let t = (height - a_height) / (b_height - a_height);
+            Point2::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t)
+        };
+        let mut loops = Vec::new();
+        while let Some(&start) = neighbours.keys().next() {
+            let mut outline = Vec::new();
+            let mut current = Some(start);
+            while let Some(edge) = current {
+                let Some(next) = neighbours.remove(&edge) else {
+                    break;
+                };
+                outline.push(crossing_point(edge));
+                current = next.into_iter().find(|next| neighbours.contains_key(next));
+            }
+            if outline.len() >= 3 {
+                loops.push(outline);
+            }
+        }
+        loops
+    }
+}
+
+/// Turns an affector's collider into a footprint on the navmesh plane.
+pub trait ColliderFootprint: Send + Sync + 'static {
+    type Collider: Component;
+
+    fn footprint(
+        collider: &Self::Collider,
+        transform: &GlobalTransform,
+        plane: NavMeshPlane,
+    ) -> Vec<Footprint>;
+
+    /// The lowest and highest heights the affector reaches, used to pick the navmesh layers it's
+    /// part of. `None` puts it in every layer.
+    fn vertical_extent(
+        _collider: &Self::Collider,
+        _transform: &GlobalTransform,
+        _plane: NavMeshPlane,
+    ) -> Option<(f32, f32)> {
+        None
+    }
+}
+
+/// Projects a [`PolyanyaCollider`] onto the XZ plane.
+pub struct XzFootprint<C>(PhantomData<C>);
+
+impl<C: PolyanyaCollider> ColliderFootprint for XzFootprint<C> {
+    type Collider = C;
+
+    fn footprint(
+        collider: &C,
+        transform: &GlobalTransform,
+        _plane: NavMeshPlane,
+    ) -> Vec<Footprint> {
+        crate::handle_shape(collider.into_typed_shape(), transform)
+    }
+
+    fn vertical_extent(
+        collider: &C,
+        transform: &GlobalTransform,
+        plane: NavMeshPlane,
+    ) -> Option<(f32, f32)> {
+        let aabb = collider.t_compute_local_aabb();
+        let heights = aabb.vertices().map(|corner| {
+            plane.height(transform.transform_point(Vec3::new(corner.x, corner.y, corner.z)))
+        });
+        let min = heights.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = heights.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        Some((min, max))
+    }
+}
+
+/// Uses a [`PolyanyaCollider2d`] as is on the XY plane.
+#[cfg(feature = "2d")]
+pub struct XyFootprint<C>(PhantomData<C>);
+
+#[cfg(feature = "2d")]
+impl<C: PolyanyaCollider2d> ColliderFootprint for XyFootprint<C> {
+    type Collider = C;
+
+    fn footprint(
+        collider: &C,
+        transform: &GlobalTransform,
+        _plane: NavMeshPlane,
+    ) -> Vec<Footprint> {
+        crate::handle_shape_2d(collider.into_typed_shape(), transform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{Quat, Transform};
+
+    use super::*;
+    use crate::utils::point_in_polygon;
+
+    fn covered(outlines: &[Vec<Point2<f32>>], (x, y): (f32, f32)) -> bool {
+        outlines
+            .iter()
+            .any(|outline| point_in_polygon(Point2::new(x, y), outline))
+    }
+
+    /// A closed prism over `outline`, whose caps are fanned out from its first point.
+    fn prism(outline: &[Vec2], bottom: f32, top: f32) -> SolidMesh {
+        let count = outline.len() as u32;
+        let vertices: Vec<Vec3> = [bottom, top]
+            .iter()
+            .flat_map(|&height| {
+                outline
+                    .iter()
+                    .map(move |point| Vec3::new(point.x, height, point.y))
+            })
+            .collect();
+        let mut triangles = Vec::new();
+        for i in 0..count {
+            let j = (i + 1) % count;
+            triangles.push([i, j, count + j]);
+            triangles.push([i, count + j, count + i]);
+        }
+        for i in 1..count - 1 {
+            triangles.push([0, i + 1, i]);
+            triangles.push([count, count + i, count + i + 1]);
+        }
+        SolidMesh::from_trimesh(&vertices, &triangles, NavMeshPlane::Xz)
+    }
+
+    fn solid(
+        (vertices, triangles): (Vec<parry3d::math::Point<f32>>, Vec<[u32; 3]>),
+        transform: Transform,
+    ) -> ConvexSolid {
+        let vertices: Vec<Vec3> = vertices
+            .iter()
+            .map(|point| transform.transform_point(Vec3::new(point.x, point.y, point.z)))
+            .collect();
+        ConvexSolid::from_trimesh(&vertices, &triangles, NavMeshPlane::Xz)
+    }
+
+    fn area(outline: &[Point2<f32>]) -> f32 {
+        let mut area = 0.0;
+        for (i, a) in outline.iter().enumerate() {
+            let b = outline[(i + 1) % outline.len()];
+            area += a.x * b.y - b.x * a.y;
+        }
+        area * 0.5
+    }
+
+    fn max_distance(outline: &[Point2<f32>]) -> f32 {
+        outline
+            .iter()
+            .map(|point| Vec2::new(point.x, point.y).length())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn cones_are_sliced_where_the_agent_is() {
+        // The base is at -1 with a radius of 1 and the tip at 1.
+        let cone = solid(
+            parry3d::shape::Cone::new(1.0, 1.0).to_trimesh(32),
+            Transform::IDENTITY,
+        );
+
+        // The widest part of the band is at its bottom, the cone's base.
+        let base = cone.slice(-1.0, 0.0);
+        assert!((max_distance(&base) - 1.0).abs() < 1e-4);
+        assert!(area(&base) > 0.0);
+
+        // Only the tip, whose radius is 0.25 at the bottom of the band.
+        let tip = cone.slice(0.5, 2.0);
+        assert!((max_distance(&tip) - 0.25).abs() < 1e-4);
+
+        assert!(cone.slice(1.5, 3.0).is_empty());
+        assert!(cone.slice(-3.0, -1.5).is_empty());
+    }
+
+    #[test]
+    fn rotated_cuboids_are_sliced_where_the_agent_is() {
+        // Stood on end, 4 tall and 1 wide.
+        let cuboid = parry3d::shape::Cuboid::new(parry3d::math::Vector::new(2.0, 0.5, 0.5));
+        let upright = solid(
+            cuboid.to_trimesh(),
+            Transform::from_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)),
+        );
+        let slice = upright.slice(0.0, 1.0);
+        assert!((area(&slice) - 1.0).abs() < 1e-4);
+        assert!(slice
+            .iter()
+            .all(|point| point.x.abs() < 0.5 + 1e-4 && point.y.abs() < 0.5 + 1e-4));
+        assert!(upright.slice(2.5, 4.0).is_empty());
+
+        // Tipped over by 45 degrees, a band through its middle is wider than the cuboid is thick.
+        let tilted = solid(
+            cuboid.to_trimesh(),
+            Transform::from_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+        );
+        let middle = tilted.slice(-0.25, 0.25);
+        let width = middle.iter().map(|point| point.x).fold(f32::MIN, f32::max)
+            - middle.iter().map(|point| point.x).fold(f32::MAX, f32::min);
+        // The cuboid's thickness spans sqrt(2) across, and the band adds its own height.
+        let expected = std::f32::consts::SQRT_2 + 0.5;
+        assert!((width - expected).abs() < 1e-4, "{} != {}", width, expected);
+        assert!(tilted.slice(3.0, 4.0).is_empty());
+    }
+
+    #[test]
+    fn concave_meshes_keep_their_shape() {
+        // An L with its reflex corner first, so the fanned caps stay inside it.
+        let outline = [
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 2.0),
+            Vec2::new(0.0, 2.0),
+            Vec2::new(0.0, 0.0),
+            Vec2::new(2.0, 0.0),
+            Vec2::new(2.0, 1.0),
+        ];
+
+        // Standing on the ground, the bottom cap is in the band.
+        let outlines = prism(&outline, 0.0, 1.0).slice(0.0, 2.0);
+        assert!(covered(&outlines, (0.3, 1.5)));
+        assert!(covered(&outlines, (1.5, 0.3)));
+        assert!(!covered(&outlines, (1.5, 1.5)));
+
+        // Sunk into the ground and taller than the agent, only the vertical sides are in the band.
+        let outlines = prism(&outline, -1.0, 3.0).slice(0.0, 2.0);
+        assert!(covered(&outlines, (0.3, 1.5)));
+        assert!(covered(&outlines, (1.5, 0.3)));
+        assert!(!covered(&outlines, (1.5, 1.5)));
+
+        // Overhead.
+        assert!(prism(&outline, 3.0, 4.0).slice(0.0, 2.0).is_empty());
+    }
+}
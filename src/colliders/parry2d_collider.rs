@@ -0,0 +1,22 @@
+use bevy::prelude::Component;
+use parry2d::{
+    bounding_volume::Aabb,
+    shape::{SharedShape, TypedShape},
+};
+
+use crate::colliders::PolyanyaCollider2d;
+
+#[derive(Component)]
+pub struct Parry2dCollider {
+    pub collider: SharedShape,
+}
+
+impl PolyanyaCollider2d for Parry2dCollider {
+    fn into_typed_shape(&self) -> TypedShape {
+        self.collider.as_typed_shape()
+    }
+
+    fn t_compute_local_aabb(&self) -> Aabb {
+        self.collider.compute_local_aabb()
+    }
+}
@@ -1,14 +1,73 @@
-use bevy_rapier3d::prelude::Collider as RapierCollider;
-use parry3d::{bounding_volume::Aabb, shape::TypedShape};
+use bevy_rapier3d::{geometry::ColliderView, prelude::Collider as RapierCollider};
+use parry3d::{
+    bounding_volume::Aabb,
+    shape::{Shape, TypedShape},
+};
 
-use super::OxidizedCollider;
+use crate::PolyanyaCollider;
 
-impl OxidizedCollider for RapierCollider {
+impl PolyanyaCollider for RapierCollider {
     fn into_typed_shape(&self) -> TypedShape {
-        self.raw.as_typed_shape()
+        unscaled_shape(self).as_typed_shape()
     }
 
     fn t_compute_local_aabb(&self) -> Aabb {
-        self.raw.compute_local_aabb()
+        unscaled_shape(self).compute_local_aabb()
     }
-}
\ No newline at end of file
+}
+
+/// The collider's shape before bevy_rapier applied the entity's scale to it. The footprint is
+/// placed with the entity's [`GlobalTransform`](bevy::prelude::GlobalTransform), which already
+/// holds that scale.
+fn unscaled_shape(collider: &RapierCollider) -> &dyn Shape {
+    match collider.as_unscaled_typed_shape() {
+        ColliderView::Ball(view) => view.raw,
+        ColliderView::Cuboid(view) => view.raw,
+        ColliderView::Capsule(view) => view.raw,
+        ColliderView::Segment(view) => view.raw,
+        ColliderView::Triangle(view) => view.raw,
+        ColliderView::TriMesh(view) => view.raw,
+        ColliderView::Polyline(view) => view.raw,
+        ColliderView::HalfSpace(view) => view.raw,
+        ColliderView::HeightField(view) => view.raw,
+        ColliderView::Compound(view) => view.raw,
+        ColliderView::ConvexPolyhedron(view) => view.raw,
+        ColliderView::Cylinder(view) => view.raw,
+        ColliderView::Cone(view) => view.raw,
+        ColliderView::RoundCuboid(view) => view.raw,
+        ColliderView::RoundTriangle(view) => view.raw,
+        ColliderView::RoundCylinder(view) => view.raw,
+        ColliderView::RoundCone(view) => view.raw,
+        ColliderView::RoundConvexPolyhedron(view) => view.raw,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{GlobalTransform, Transform, Vec3};
+
+    use super::*;
+    use crate::colliders::{ColliderFootprint, Footprint, NavMeshPlane, XzFootprint};
+
+    #[test]
+    fn scaled_colliders_are_only_scaled_once() {
+        let mut collider = RapierCollider::cuboid(1.0, 1.0, 1.0);
+        let transform = GlobalTransform::from(Transform::from_scale(Vec3::splat(2.0)));
+        // What bevy_rapier does to the collider of an entity with this transform.
+        collider.set_scale(Vec3::splat(2.0), 10);
+
+        let extent =
+            XzFootprint::<RapierCollider>::vertical_extent(&collider, &transform, NavMeshPlane::Xz);
+        assert_eq!(extent, Some((-2.0, 2.0)));
+
+        let footprints =
+            XzFootprint::<RapierCollider>::footprint(&collider, &transform, NavMeshPlane::Xz);
+        let [Footprint::Solid(solid)] = footprints.as_slice() else {
+            panic!("expected one solid footprint, got {:?}", footprints);
+        };
+        for point in solid.slice(f32::NEG_INFINITY, f32::INFINITY) {
+            assert!((point.x.abs() - 2.0).abs() < 1e-4, "{:?}", point);
+            assert!((point.y.abs() - 2.0).abs() < 1e-4, "{:?}", point);
+        }
+    }
+}
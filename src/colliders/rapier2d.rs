@@ -0,0 +1,65 @@
+use bevy_rapier2d::{geometry::ColliderView, prelude::Collider as Rapier2dCollider};
+use parry2d::{
+    bounding_volume::Aabb,
+    shape::{Shape, TypedShape},
+};
+
+use crate::colliders::PolyanyaCollider2d;
+
+impl PolyanyaCollider2d for Rapier2dCollider {
+    fn into_typed_shape(&self) -> TypedShape {
+        unscaled_shape(self).as_typed_shape()
+    }
+
+    fn t_compute_local_aabb(&self) -> Aabb {
+        unscaled_shape(self).compute_local_aabb()
+    }
+}
+
+/// The collider's shape before bevy_rapier applied the entity's scale to it. The footprint is
+/// placed with the entity's [`GlobalTransform`](bevy::prelude::GlobalTransform), which already
+/// holds that scale.
+fn unscaled_shape(collider: &Rapier2dCollider) -> &dyn Shape {
+    match collider.as_unscaled_typed_shape() {
+        ColliderView::Ball(view) => view.raw,
+        ColliderView::Cuboid(view) => view.raw,
+        ColliderView::Capsule(view) => view.raw,
+        ColliderView::Segment(view) => view.raw,
+        ColliderView::Triangle(view) => view.raw,
+        ColliderView::TriMesh(view) => view.raw,
+        ColliderView::Polyline(view) => view.raw,
+        ColliderView::HalfSpace(view) => view.raw,
+        ColliderView::HeightField(view) => view.raw,
+        ColliderView::Compound(view) => view.raw,
+        ColliderView::ConvexPolygon(view) => view.raw,
+        ColliderView::RoundCuboid(view) => view.raw,
+        ColliderView::RoundTriangle(view) => view.raw,
+        ColliderView::RoundConvexPolygon(view) => view.raw,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::{GlobalTransform, Transform, Vec3};
+
+    use super::*;
+    use crate::colliders::{ColliderFootprint, Footprint, NavMeshPlane, XyFootprint};
+
+    #[test]
+    fn scaled_colliders_are_only_scaled_once() {
+        let mut collider = Rapier2dCollider::cuboid(1.0, 1.0);
+        let transform = GlobalTransform::from(Transform::from_scale(Vec3::new(2.0, 2.0, 1.0)));
+        // What bevy_rapier does to the collider of an entity with this transform.
+        collider.set_scale(bevy::prelude::Vec2::splat(2.0), 10);
+
+        let footprints =
+            XyFootprint::<Rapier2dCollider>::footprint(&collider, &transform, NavMeshPlane::Xy);
+        let [Footprint::Closed(outline)] = footprints.as_slice() else {
+            panic!("expected one closed footprint, got {:?}", footprints);
+        };
+        for point in outline {
+            assert!((point.x.abs() - 2.0).abs() < 1e-4, "{:?}", point);
+            assert!((point.y.abs() - 2.0).abs() < 1e-4, "{:?}", point);
+        }
+    }
+}
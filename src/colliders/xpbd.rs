@@ -1,14 +1,14 @@
 use bevy_xpbd_3d::prelude::Collider as XpbdCollider;
 use parry3d::{bounding_volume::Aabb, shape::TypedShape};
 
-use super::OxidizedCollider;
+use crate::PolyanyaCollider;
 
-impl OxidizedCollider for XpbdCollider {
+impl PolyanyaCollider for XpbdCollider {
     fn into_typed_shape(&self) -> TypedShape {
-        self.as_typed_shape()
+        self.get_shape().as_typed_shape()
     }
 
     fn t_compute_local_aabb(&self) -> Aabb {
-        self.compute_local_aabb()
+        self.get_shape().compute_local_aabb()
     }
-}
\ No newline at end of file
+}
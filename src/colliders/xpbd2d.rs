@@ -0,0 +1,14 @@
+use bevy_xpbd_2d::prelude::Collider as Xpbd2dCollider;
+use parry2d::{bounding_volume::Aabb, shape::TypedShape};
+
+use crate::colliders::PolyanyaCollider2d;
+
+impl PolyanyaCollider2d for Xpbd2dCollider {
+    fn into_typed_shape(&self) -> TypedShape {
+        self.get_shape().as_typed_shape()
+    }
+
+    fn t_compute_local_aabb(&self) -> Aabb {
+        self.get_shape().compute_local_aabb()
+    }
+}
@@ -0,0 +1,632 @@
+use std::time::Duration;
+
+use bevy::{ecs::query::Has, prelude::*, utils::HashSet};
+use spade::{Point2, Triangulation};
+
+use crate::{
+    colliders::{ColliderFootprint, Footprint, NavMeshPlane},
+    footprint_ground_height,
+    pathfinding::{NavPath, OffMeshLink},
+    utils::{AffectorKind, NavMeshAffector, NavMeshArea, NavMeshWalkable},
+    NavHeightField, NavMeshLayer, NavMeshSettings, NavMeshUpdated, PolyNavMesh,
+};
+
+/// Draws the navmesh and what went into it with gizmos, as configured by [`NavMeshDebugSettings`].
+#[derive(Default)]
+pub struct NavMeshDebugPlugin;
+
+impl Plugin for NavMeshDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavMeshDebugSettings>().add_systems(
+            Update,
+            (draw_navmesh, draw_paths, draw_off_mesh_links)
+                .run_if(|settings: Res<NavMeshDebugSettings>| settings.enabled),
+        );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct NavMeshDebugSettings {
+    pub enabled: bool,
+    /// Navigable triangles of the triangulation.
+    pub triangles: bool,
+    /// Triangles of the triangulation inside obstacles or outside walkable ground.
+    pub blocked_triangles: bool,
+    pub constraints: bool,
+    /// The polyanya polygons that [`PathMesh`](bevy_pathmesh::PathMesh) queries run on.
+    pub polygons: bool,
+    /// Each polygon's index in [`PolyNavMesh::graph`].
+    pub polygon_indices: bool,
+    /// The outline each affector cuts into the navmesh.
+    pub footprints: bool,
+    /// Every entity's [`NavPath`].
+    pub paths: bool,
+    pub off_mesh_links: bool,
+    /// Highlights the triangles and polygons the last [`NavMeshUpdated`] changed, with a line to
+    /// each affector that caused it labelled with its entity index.
+    pub changed_region: bool,
+    /// How long the changed region stays highlighted, in seconds.
+    pub changed_region_duration: f32,
+    /// Lines are drawn this far above the [`NavHeightField`], or above the layer's floor where
+    /// there is no terrain.
+    pub height_offset: f32,
+    /// Height of the digits of polygon indices.
+    pub index_size: f32,
+    pub colours: NavMeshDebugColours,
+}
+
+impl Default for NavMeshDebugSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            triangles: true,
+            blocked_triangles: true,
+            constraints: true,
+            polygons: false,
+            polygon_indices: false,
+            footprints: false,
+            paths: true,
+            off_mesh_links: true,
+            changed_region: true,
+            changed_region_duration: 3.0,
+            height_offset: 0.1,
+            index_size: 0.5,
+            colours: default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NavMeshDebugColours {
+    pub navigable: Color,
+    pub blocked: Color,
+    pub constraint: Color,
+    pub polygon: Color,
+    pub polygon_index: Color,
+    pub obstacle: Color,
+    pub walkable: Color,
+    pub area: Color,
+    pub path: Color,
+    pub off_mesh_link: Color,
+    pub changed: Color,
+}
+
+impl Default for NavMeshDebugColours {
+    fn default() -> Self {
+        Self {
+            navigable: Color::GREEN,
+            blocked: Color::RED,
+            constraint: Color::BLACK,
+            polygon: Color::BLUE,
+            polygon_index: Color::WHITE,
+            obstacle: Color::ORANGE,
+            walkable: Color::CYAN,
+            area: Color::YELLOW,
+            path: Color::FUCHSIA,
+            off_mesh_link: Color::PURPLE,
+            changed: Color::GOLD,
+        }
+    }
+}
+
+/// World space lines of the navmesh, rebuilt whenever it changes so the heights along the
+/// terrain aren't sampled every frame.
+#[derive(Default)]
+struct NavMeshLines {
+    navigable: Vec<[Vec3; 3]>,
+    blocked: Vec<[Vec3; 3]>,
+    constraints: Vec<[Vec3; 2]>,
+    polygons: Vec<Vec<Vec3>>,
+    polygon_centres: Vec<Vec3>,
+}
+
+impl NavMeshLines {
+    fn new(
+        navmesh: &PolyNavMesh,
+        heightfield: Option<&NavHeightField>,
+        height_offset: f32,
+    ) -> Self {
+        let mut lines = Self::default();
+        for (layer, mesh) in navmesh.layers.iter().zip(&navmesh.meshes) {
+            let to_world = |point: Point2<f32>| {
+                let point = Vec2::new(point.x, point.y);
+                let height = surface_height(heightfield, &layer.layer, navmesh.plane, point);
+                navmesh.plane.to_world(point, height + height_offset)
+            };
+
+            for face in layer.cdt.inner_faces() {
+                let triangle = face.positions().map(to_world);
+                if layer.navigable_faces.contains_key(&face.index()) {
+                    lines.navigable.push(triangle);
+                    // Matches the order polygons are added to the graph in.
+                    lines.polygon_centres.push(to_world(face.center()));
+                } else {
+                    lines.blocked.push(triangle);
+                }
+            }
+            lines.constraints.extend(
+                layer
+                    .cdt
+                    .undirected_edges()
+                    .filter(|edge| edge.is_constraint_edge())
+                    .map(|edge| edge.positions().map(to_world)),
+            );
+
+            lines.polygons.extend(mesh.polygons.iter().map(|polygon| {
+                polygon
+                    .vertices
+                    .iter()
+                    .map(|&vertex| {
+                        let coords = mesh.vertices[vertex].coords;
+                        to_world(Point2::new(coords.x, coords.y))
+                    })
+                    .collect()
+            }));
+        }
+        lines
+    }
+}
+
+/// What the last [`NavMeshUpdated`] changed, highlighted until `expires`.
+#[derive(Default)]
+struct ChangedRegion {
+    expires: Duration,
+    triangles: Vec<[Vec3; 3]>,
+    polygons: Vec<Vec<Vec3>>,
+    affectors: Vec<Entity>,
+}
+
+impl ChangedRegion {
+    fn new(
+        previous: &NavMeshLines,
+        current: &NavMeshLines,
+        affectors: Vec<Entity>,
+        expires: Duration,
+    ) -> Self {
+        // A triangle that became blocked has changed even though it's in the same place.
+        let previous_triangles: HashSet<_> = previous
+            .navigable
+            .iter()
+            .map(|triangle| (triangle, true))
+            .chain(previous.blocked.iter().map(|triangle| (triangle, false)))
+            .map(|(triangle, navigable)| (shape_key(triangle), navigable))
+            .collect();
+        let triangles = current
+            .navigable
+            .iter()
+            .map(|triangle| (triangle, true))
+            .chain(current.blocked.iter().map(|triangle| (triangle, false)))
+            .filter(|(triangle, navigable)| {
+                !previous_triangles.contains(&(shape_key(*triangle), *navigable))
+            })
+            .map(|(triangle, _)| *triangle)
+            .collect();
+
+        let previous_polygons: HashSet<_> = previous
+            .polygons
+            .iter()
+            .map(|polygon| shape_key(polygon))
+            .collect();
+        let polygons = current
+            .polygons
+            .iter()
+            .filter(|polygon| !previous_polygons.contains(&shape_key(polygon)))
+            .cloned()
+            .collect();
+
+        Self {
+            expires,
+            triangles,
+            polygons,
+            affectors,
+        }
+    }
+}
+
+/// Identifies a shape by its points regardless of which one it starts from.
+fn shape_key(points: &[Vec3]) -> Vec<[u32; 3]> {
+    let mut key: Vec<_> = points
+        .iter()
+        .map(|point| point.to_array().map(f32::to_bits))
+        .collect();
+    key.sort_unstable();
+    key
+}
+
+/// Where to draw a point of `layer`: on the terrain if the layer is at the terrain's height,
+/// otherwise on the layer's floor.
+fn surface_height(
+    heightfield: Option<&NavHeightField>,
+    layer: &NavMeshLayer,
+    plane: NavMeshPlane,
+    point: Vec2,
+) -> f32 {
+    let terrain = match plane {
+        NavMeshPlane::Xz => heightfield.and_then(|heightfield| heightfield.height_at(point)),
+        NavMeshPlane::Xy => None,
+    };
+    match terrain {
+        Some(height) if layer.contains_height(height) => height,
+        _ if layer.floor.is_finite() => layer.floor,
+        _ => 0.0,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_navmesh(
+    settings: Res<NavMeshDebugSettings>,
+    navmesh: Option<Res<PolyNavMesh>>,
+    heightfield: Option<Res<NavHeightField>>,
+    time: Res<Time>,
+    mut updated_events: EventReader<NavMeshUpdated>,
+    transform_query: Query<&GlobalTransform>,
+    mut lines: Local<NavMeshLines>,
+    mut changed: Local<ChangedRegion>,
+    mut gizmos: Gizmos,
+) {
+    let Some(navmesh) = navmesh else {
+        return;
+    };
+    let mut updated = false;
+    let mut affectors = Vec::new();
+    for event in updated_events.iter() {
+        updated = true;
+        affectors.extend(event.affectors.iter().copied());
+    }
+    let heightfield_changed = heightfield
+        .as_ref()
+        .map_or(false, |heightfield| heightfield.is_changed());
+    if navmesh.is_changed() || heightfield_changed || settings.is_changed() {
+        let previous = std::mem::replace(
+            &mut *lines,
+            NavMeshLines::new(&navmesh, heightfield.as_deref(), settings.height_offset),
+        );
+        if updated {
+            let expires =
+                time.elapsed() + Duration::from_secs_f32(settings.changed_region_duration);
+            *changed = ChangedRegion::new(&previous, &lines, affectors, expires);
+        }
+    }
+
+    let colours = &settings.colours;
+    let mut draw_triangles = |triangles: &[[Vec3; 3]], colour: Color| {
+        for &[a, b, c] in triangles {
+            gizmos.linestrip([a, b, c, a], colour);
+        }
+    };
+    if settings.triangles {
+        draw_triangles(&lines.navigable, colours.navigable);
+    }
+    if settings.blocked_triangles {
+        draw_triangles(&lines.blocked, colours.blocked);
+    }
+    if settings.polygons {
+        for polygon in &lines.polygons {
+            gizmos.linestrip(
+                polygon.iter().chain(polygon.first()).copied(),
+                colours.polygon,
+            );
+        }
+    }
+    if settings.constraints {
+        for &[a, b] in &lines.constraints {
+            gizmos.line(a, b, colours.constraint);
+        }
+    }
+    if settings.polygon_indices {
+        let right = navmesh.plane.to_world(Vec2::X, 0.0);
+        let up = navmesh.plane.to_world(Vec2::Y, 0.0);
+        for (index, centre) in lines.polygon_centres.iter().enumerate() {
+            draw_number(
+                &mut gizmos,
+                index,
+                *centre,
+                (right, up),
+                settings.index_size,
+                colours.polygon_index,
+            );
+        }
+    }
+
+    if settings.changed_region && time.elapsed() < changed.expires {
+        // Lifted so the highlight isn't hidden by the lines it's on top of.
+        let normal = navmesh.plane.to_world(Vec2::ZERO, 1.0);
+        let lift = normal * settings.height_offset;
+        for &[a, b, c] in &changed.triangles {
+            gizmos.linestrip([a, b, c, a].map(|point| point + lift), colours.changed);
+        }
+        if settings.polygons {
+            for polygon in &changed.polygons {
+                gizmos.linestrip(
+                    polygon
+                        .iter()
+                        .chain(polygon.first())
+                        .map(|point| *point + lift),
+                    colours.changed,
+                );
+            }
+        }
+
+        let points = changed.triangles.iter().flatten();
+        let centre = points.clone().sum::<Vec3>() / points.count().max(1) as f32 + lift;
+        let right = navmesh.plane.to_world(Vec2::X, 0.0);
+        let up = navmesh.plane.to_world(Vec2::Y, 0.0);
+        for &entity in &changed.affectors {
+            // The affector may have been despawned since.
+            let Ok(transform) = transform_query.get(entity) else {
+                continue;
+            };
+            let position = transform.translation();
+            let label = position + normal * 2.0;
+            gizmos.line(position, label, colours.changed);
+            gizmos.line(label, centre, colours.changed);
+            draw_number(
+                &mut gizmos,
+                entity.index() as usize,
+                label + normal * settings.index_size,
+                (right, up),
+                settings.index_size,
+                colours.changed,
+            );
+        }
+    }
+}
+
+/// Which of the seven segments, from top clockwise with the middle last, make up each digit.
+const DIGIT_SEGMENTS: [u8; 10] = [
+    0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110, 0b1101101, 0b1111101, 0b0000111,
+    0b1111111, 0b1101111,
+];
+
+/// Draws `number` as seven-segment digits centred on `centre`, since gizmos can't draw text.
+fn draw_number(
+    gizmos: &mut Gizmos,
+    number: usize,
+    centre: Vec3,
+    (right, up): (Vec3, Vec3),
+    size: f32,
+    colour: Color,
+) {
+    let digits = number.to_string();
+    let width = size * 0.5;
+    let advance = width * 1.5;
+    let start = centre
+        - right * (advance * digits.len() as f32 - (advance - width)) * 0.5
+        - up * size * 0.5;
+    for (i, digit) in digits.bytes().enumerate() {
+        let origin = start + right * advance * i as f32;
+        let corner = |x: f32, y: f32| origin + right * width * x + up * size * y;
+        let segments = [
+            (corner(0.0, 1.0), corner(1.0, 1.0)),
+            (corner(1.0, 1.0), corner(1.0, 0.5)),
+            (corner(1.0, 0.5), corner(1.0, 0.0)),
+            (corner(0.0, 0.0), corner(1.0, 0.0)),
+            (corner(0.0, 0.5), corner(0.0, 0.0)),
+            (corner(0.0, 1.0), corner(0.0, 0.5)),
+            (corner(0.0, 0.5), corner(1.0, 0.5)),
+        ];
+        let mask = DIGIT_SEGMENTS[(digit - b'0') as usize];
+        for (segment, (a, b)) in segments.into_iter().enumerate() {
+            if mask & (1 << segment) != 0 {
+                gizmos.line(a, b, colour);
+            }
+        }
+    }
+}
+
+fn draw_paths(
+    settings: Res<NavMeshDebugSettings>,
+    navmesh: Option<Res<PolyNavMesh>>,
+    plane: Res<NavMeshPlane>,
+    heightfield: Option<Res<NavHeightField>>,
+    path_query: Query<&NavPath>,
+    mut gizmos: Gizmos,
+) {
+    if !settings.paths {
+        return;
+    }
+    // Each waypoint is drawn on the layer it's on, falling back to the terrain.
+    let layer_of = |layer: Option<&usize>| {
+        navmesh
+            .as_ref()
+            .zip(layer)
+            .and_then(|(navmesh, &layer)| navmesh.layers.get(layer))
+            .map_or_else(NavMeshLayer::default, |layer| layer.layer)
+    };
+    for path in path_query.iter() {
+        let points = path.path.iter().enumerate().map(|(i, point)| {
+            let layer = layer_of(path.layers.get(i));
+            let height = surface_height(heightfield.as_deref(), &layer, *plane, *point);
+            plane.to_world(*point, height + settings.height_offset)
+        });
+        gizmos.linestrip(points, settings.colours.path);
+    }
+}
+
+fn draw_off_mesh_links(
+    settings: Res<NavMeshDebugSettings>,
+    plane: Res<NavMeshPlane>,
+    link_query: Query<(&OffMeshLink, &GlobalTransform)>,
+    mut gizmos: Gizmos,
+) {
+    if !settings.off_mesh_links {
+        return;
+    }
+    const SEGMENTS: usize = 8;
+    let normal = plane.to_world(Vec2::ZERO, 1.0);
+    let colour = settings.colours.off_mesh_link;
+    for (link, transform) in link_query.iter() {
+        let start = transform.transform_point(link.start);
+        let end = transform.transform_point(link.end);
+        // Arc over the gap so links stand out from the navmesh under them.
+        let rise = start.distance(end) * 0.25;
+        gizmos.linestrip(
+            (0..=SEGMENTS).map(|i| {
+                let t = i as f32 / SEGMENTS as f32;
+                start.lerp(end, t) + normal * rise * 4.0 * t * (1.0 - t)
+            }),
+            colour,
+        );
+
+        let mut draw_arrow_head = |tip: Vec3, from: Vec3| {
+            let direction = (tip - from).normalize_or_zero();
+            let side = direction.cross(normal).normalize_or_zero();
+            let size = (rise * 0.5).min(0.5);
+            let back = tip - direction * size;
+            gizmos.line(tip, back + side * size * 0.5, colour);
+            gizmos.line(tip, back - side * size * 0.5, colour);
+        };
+        draw_arrow_head(end, start);
+        if link.bidirectional {
+            draw_arrow_head(start, end);
+        }
+    }
+}
+
+/// Registered for each collider type by [`add_navmesh_collider`](crate::add_navmesh_collider).
+pub(crate) fn draw_footprints<F: ColliderFootprint>(
+    settings: Res<NavMeshDebugSettings>,
+    navmesh_settings: Res<NavMeshSettings>,
+    plane: Res<NavMeshPlane>,
+    heightfield: Option<Res<NavHeightField>>,
+    collider_query: Query<
+        (
+            &F::Collider,
+            &GlobalTransform,
+            Has<NavMeshWalkable>,
+            Option<&NavMeshArea>,
+        ),
+        With<NavMeshAffector>,
+    >,
+    mut gizmos: Gizmos,
+) {
+    if !settings.enabled || !settings.footprints {
+        return;
+    }
+    let plane = *plane;
+    let layer = NavMeshLayer::default();
+    let to_world = |point: &Point2<f32>| {
+        let point = Vec2::new(point.x, point.y);
+        let height = surface_height(heightfield.as_deref(), &layer, plane, point);
+        plane.to_world(point, height + settings.height_offset)
+    };
+
+    for (collider, transform, walkable, area) in collider_query.iter() {
+        let kind = AffectorKind::new(walkable, area);
+        let colour = match kind {
+            AffectorKind::Walkable => settings.colours.walkable,
+            AffectorKind::Area(_) => settings.colours.area,
+            AffectorKind::Obstacle => settings.colours.obstacle,
+        };
+        for footprint in F::footprint(collider, transform, plane) {
+            let band = || {
+                let ground = heightfield.as_ref().and_then(|heightfield| {
+                    footprint_ground_height(&heightfield.heightfield, &footprint)
+                });
+                layer.slice_band(kind, ground, navmesh_settings.agent_height)
+            };
+            let (outlines, closed) = match &footprint {
+                Footprint::Closed(points) => (vec![points.clone()], true),
+                Footprint::Open(points) => (vec![points.clone()], false),
+                Footprint::Solid(solid) => {
+                    let (bottom, top) = band();
+                    (vec![solid.slice(bottom, top)], true)
+                }
+                Footprint::Mesh(mesh) => {
+                    let (bottom, top) = band();
+                    (mesh.slice(bottom, top), true)
+                }
+            };
+            for points in outlines {
+                let points: Vec<Vec3> = points.iter().map(to_world).collect();
+                if closed {
+                    gizmos.linestrip(points.iter().chain(points.first()).copied(), colour);
+                } else {
+                    gizmos.linestrip(points, colour);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, utils::AffectorKind};
+
+    /// The lines of a 10 by 10 navmesh with a 1 by 1 square of `kind` from `min`.
+    fn lines(kind: AffectorKind, min: (f32, f32)) -> NavMeshLines {
+        let square = testing::rectangle(min, (min.0 + 1.0, min.1 + 1.0));
+        let layer = testing::layer((10.0, 10.0), &[(kind, square)]);
+        NavMeshLines::new(&testing::navmesh((10.0, 10.0), vec![layer]), None, 0.0)
+    }
+
+    fn in_square(triangle: &[Vec3; 3], min: (f32, f32)) -> bool {
+        let centre = (triangle[0] + triangle[1] + triangle[2]) / 3.0;
+        (min.0..min.0 + 1.0).contains(&centre.x) && (min.1..min.1 + 1.0).contains(&centre.z)
+    }
+
+    #[test]
+    fn shapes_are_the_same_from_any_point() {
+        let [a, b, c] = [Vec3::X, Vec3::Y, Vec3::Z];
+        assert_eq!(shape_key(&[a, b, c]), shape_key(&[b, c, a]));
+        assert_ne!(shape_key(&[a, b, c]), shape_key(&[a, b, Vec3::ONE]));
+    }
+
+    #[test]
+    fn unchanged_navmeshes_have_no_changed_region() {
+        let lines = lines(AffectorKind::Obstacle, (2.0, 2.0));
+        let region = ChangedRegion::new(&lines, &lines, Vec::new(), Duration::ZERO);
+        assert!(region.triangles.is_empty());
+        assert!(region.polygons.is_empty());
+    }
+
+    #[test]
+    fn triangles_that_become_blocked_have_changed() {
+        let road = NavMeshArea { id: 1, cost: 0.5 };
+        let previous = lines(AffectorKind::Area(road), (2.0, 2.0));
+        let current = lines(AffectorKind::Obstacle, (2.0, 2.0));
+        let affector = Entity::from_raw(3);
+        let expires = Duration::from_secs(2);
+        let region = ChangedRegion::new(&previous, &current, vec![affector], expires);
+
+        // Both are cut the same way, only the square's triangles are blocked now.
+        let blocked = current
+            .blocked
+            .iter()
+            .filter(|triangle| in_square(triangle, (2.0, 2.0)))
+            .count();
+        assert!(blocked > 0);
+        assert_eq!(region.triangles.len(), blocked);
+        assert!(region
+            .triangles
+            .iter()
+            .all(|triangle| in_square(triangle, (2.0, 2.0))));
+        assert_eq!(region.affectors, vec![affector]);
+        assert_eq!(region.expires, expires);
+    }
+
+    #[test]
+    fn moved_obstacles_change_both_places() {
+        let previous = lines(AffectorKind::Obstacle, (2.0, 2.0));
+        let current = lines(AffectorKind::Obstacle, (-3.0, -3.0));
+        let region = ChangedRegion::new(&previous, &current, Vec::new(), Duration::ZERO);
+
+        // The new square is blocked, and the polygons are cut around it instead of the old one.
+        assert!(region
+            .triangles
+            .iter()
+            .any(|triangle| in_square(triangle, (-3.0, -3.0))));
+        assert!(!region.polygons.is_empty());
+
+        let previous_polygons: Vec<_> = previous
+            .polygons
+            .iter()
+            .map(|polygon| shape_key(polygon))
+            .collect();
+        assert!(region
+            .polygons
+            .iter()
+            .all(|polygon| !previous_polygons.contains(&shape_key(polygon))));
+    }
+}
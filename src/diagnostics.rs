@@ -0,0 +1,84 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics, RegisterDiagnostic},
+    prelude::*,
+};
+
+use crate::{NavMeshBuildStats, NavMeshGenerated, NavMeshUpdated};
+
+/// Adds navmesh build diagnostics to an App. These are recorded whenever a build completes.
+#[derive(Default)]
+pub struct NavMeshDiagnosticsPlugin;
+
+impl Plugin for NavMeshDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(
+            Diagnostic::new(Self::BUILD_TIME, "navmesh_build_time", 20).with_suffix("ms"),
+        )
+        .register_diagnostic(
+            Diagnostic::new(Self::VERTEX_COUNT, "navmesh_vertex_count", 1)
+                .with_smoothing_factor(0.0),
+        )
+        .register_diagnostic(
+            Diagnostic::new(Self::POLYGON_COUNT, "navmesh_polygon_count", 1)
+                .with_smoothing_factor(0.0),
+        )
+        .register_diagnostic(
+            Diagnostic::new(Self::CONSTRAINT_COUNT, "navmesh_constraint_count", 1)
+                .with_smoothing_factor(0.0),
+        )
+        .register_diagnostic(Diagnostic::new(
+            Self::REBUILDS_PER_SECOND,
+            "navmesh_rebuilds_per_second",
+            20,
+        ))
+        .add_systems(PostUpdate, Self::diagnostic_system);
+    }
+}
+
+impl NavMeshDiagnosticsPlugin {
+    pub const BUILD_TIME: DiagnosticId =
+        DiagnosticId::from_u128(157412874839173945713296420684187146241);
+    pub const VERTEX_COUNT: DiagnosticId =
+        DiagnosticId::from_u128(21685367950317429370245651946837452306);
+    pub const POLYGON_COUNT: DiagnosticId =
+        DiagnosticId::from_u128(303478932514963016830290517351904869891);
+    pub const CONSTRAINT_COUNT: DiagnosticId =
+        DiagnosticId::from_u128(98043571630951228439013785712950647812);
+    pub const REBUILDS_PER_SECOND: DiagnosticId =
+        DiagnosticId::from_u128(240719357836429681953745021468732196357);
+
+    pub fn diagnostic_system(
+        mut diagnostics: Diagnostics,
+        time: Res<Time>,
+        mut generated_events: EventReader<NavMeshGenerated>,
+        mut updated_events: EventReader<NavMeshUpdated>,
+        mut completed_at: Local<VecDeque<Duration>>,
+    ) {
+        let now = time.raw_elapsed();
+        let stats = generated_events
+            .iter()
+            .map(|event| event.stats)
+            .chain(updated_events.iter().map(|event| event.stats));
+        for stats in stats {
+            Self::add_build_measurements(&mut diagnostics, &stats);
+            completed_at.push_back(now);
+        }
+
+        while completed_at
+            .front()
+            .map_or(false, |completed| now - *completed > Duration::from_secs(1))
+        {
+            completed_at.pop_front();
+        }
+        diagnostics.add_measurement(Self::REBUILDS_PER_SECOND, || completed_at.len() as f64);
+    }
+
+    fn add_build_measurements(diagnostics: &mut Diagnostics, stats: &NavMeshBuildStats) {
+        diagnostics.add_measurement(Self::BUILD_TIME, || stats.build_time.as_secs_f64() * 1000.0);
+        diagnostics.add_measurement(Self::VERTEX_COUNT, || stats.vertices as f64);
+        diagnostics.add_measurement(Self::POLYGON_COUNT, || stats.polygons as f64);
+        diagnostics.add_measurement(Self::CONSTRAINT_COUNT, || stats.constraints as f64);
+    }
+}
@@ -0,0 +1,334 @@
+//! Writes the generated navmesh out as Wavefront OBJ or SVG, to inspect it in other tools.
+
+use std::fmt::Write;
+
+use bevy::prelude::*;
+use spade::{Point2, Triangulation};
+
+use crate::{LayerMesh, PolyNavMesh};
+
+/// Margin around the navmesh in the SVG, in navmesh units.
+const SVG_MARGIN: f32 = 1.0;
+const NAVIGABLE_COLOUR: &str = "#6c6";
+const BLOCKED_COLOUR: &str = "#c66";
+const EDGE_COLOUR: &str = "#888";
+const CONSTRAINT_COLOUR: &str = "#222";
+const POLYGON_COLOUR: &str = "#36c";
+
+/// The triangulation of every layer, with the navigable and blocked faces and the constraint edges
+/// as separate groups. Layers are placed at their floor height, or 0 if it is unbounded.
+pub fn cdt_to_obj(navmesh: &PolyNavMesh) -> String {
+    let mut obj = String::new();
+    let mut vertex_offset = 1;
+    for (i, layer) in navmesh.layers.iter().enumerate() {
+        let height = layer_height(layer);
+        for vertex in layer.cdt.vertices() {
+            write_obj_vertex(&mut obj, navmesh, vertex.position(), height);
+        }
+
+        for navigable in [true, false] {
+            let group = if navigable { "navigable" } else { "blocked" };
+            let _ = writeln!(obj, "g layer{}_{}", i, group);
+            for face in layer.cdt.inner_faces() {
+                if layer.navigable_faces.contains_key(&face.index()) != navigable {
+                    continue;
+                }
+                let [a, b, c] = face.vertices().map(|vertex| vertex.index() + vertex_offset);
+                let _ = writeln!(obj, "f {} {} {}", a, b, c);
+            }
+        }
+
+        let _ = writeln!(obj, "g layer{}_constraints", i);
+        for edge in layer.cdt.undirected_edges() {
+            if edge.is_constraint_edge() {
+                let [a, b] = edge.vertices().map(|vertex| vertex.index() + vertex_offset);
+                let _ = writeln!(obj, "l {} {}", a, b);
+            }
+        }
+        vertex_offset += layer.cdt.num_vertices();
+    }
+    obj
+}
+
+/// The polyanya polygons that path queries run on, one group per layer.
+pub fn polygons_to_obj(navmesh: &PolyNavMesh) -> String {
+    let mut obj = String::new();
+    let mut vertex_offset = 1;
+    for (i, (layer, mesh)) in navmesh.layers.iter().zip(&navmesh.meshes).enumerate() {
+        let height = layer_height(layer);
+        for vertex in &mesh.vertices {
+            write_obj_vertex(
+                &mut obj,
+                navmesh,
+                Point2::new(vertex.coords.x, vertex.coords.y),
+                height,
+            );
+        }
+
+        let _ = writeln!(obj, "g layer{}_polygons", i);
+        for polygon in &mesh.polygons {
+            obj.push('f');
+            for vertex in &polygon.vertices {
+                let _ = write!(obj, " {}", vertex + vertex_offset);
+            }
+            obj.push('\n');
+        }
+        vertex_offset += mesh.vertices.len();
+    }
+    obj
+}
+
+/// A top down view of one layer. Faces are green where navigable and red where blocked, constraint
+/// edges are black and the polyanya polygons are outlined in blue. Returns `None` if there is no
+/// such layer, or it hasn't been built yet.
+pub fn layer_to_svg(navmesh: &PolyNavMesh, layer: usize) -> Option<String> {
+    let mesh = navmesh.meshes.get(layer)?;
+    let layer = navmesh.layers.get(layer)?;
+    let (width, height) = navmesh.dimensions;
+    let (min_x, min_y) = navmesh.origin;
+    // SVG's y axis points down, so flip the navmesh to keep it the same way round as a top down view.
+    let point = |point: Point2<f32>| format!("{},{}", point.x, -point.y);
+    let (left, top) = (min_x - SVG_MARGIN, -(min_y + height) - SVG_MARGIN);
+    let (width, height) = (width + SVG_MARGIN * 2.0, height + SVG_MARGIN * 2.0);
+    let stroke_width = width.max(height) / 1000.0;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+        left, top, width, height
+    );
+
+    let _ = writeln!(
+        svg,
+        r#"<g id="faces" stroke="{}" stroke-width="{}">"#,
+        EDGE_COLOUR, stroke_width
+    );
+    for face in layer.cdt.inner_faces() {
+        let colour = if layer.navigable_faces.contains_key(&face.index()) {
+            NAVIGABLE_COLOUR
+        } else {
+            BLOCKED_COLOUR
+        };
+        let [a, b, c] = face.positions().map(point);
+        let _ = writeln!(
+            svg,
+            r#"<polygon points="{} {} {}" fill="{}"/>"#,
+            a, b, c, colour
+        );
+    }
+    svg.push_str("</g>\n");
+
+    let _ = writeln!(
+        svg,
+        r#"<g id="polygons" fill="none" stroke="{}" stroke-width="{}">"#,
+        POLYGON_COLOUR,
+        stroke_width * 2.0
+    );
+    for polygon in &mesh.polygons {
+        let points: Vec<_> = polygon
+            .vertices
+            .iter()
+            .map(|&vertex| {
+                let coords = mesh.vertices[vertex].coords;
+                point(Point2::new(coords.x, coords.y))
+            })
+            .collect();
+        let _ = writeln!(svg, r#"<polygon points="{}"/>"#, points.join(" "));
+    }
+    svg.push_str("</g>\n");
+
+    let _ = writeln!(
+        svg,
+        r#"<g id="constraints" stroke="{}" stroke-width="{}">"#,
+        CONSTRAINT_COLOUR,
+        stroke_width * 3.0
+    );
+    for edge in layer.cdt.undirected_edges() {
+        if edge.is_constraint_edge() {
+            let [a, b] = edge.positions();
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}"/>"#,
+                a.x, -a.y, b.x, -b.y
+            );
+        }
+    }
+    svg.push_str("</g>\n</svg>\n");
+    Some(svg)
+}
+
+fn layer_height(layer: &LayerMesh) -> f32 {
+    if layer.layer.floor.is_finite() {
+        layer.layer.floor
+    } else {
+        0.0
+    }
+}
+
+fn write_obj_vertex(obj: &mut String, navmesh: &PolyNavMesh, point: Point2<f32>, height: f32) {
+    let position = navmesh.plane.to_world(Vec2::new(point.x, point.y), height);
+    let _ = writeln!(obj, "v {} {} {}", position.x, position.y, position.z);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, utils::AffectorKind, NavMeshLayer};
+
+    /// Two 10 by 10 layers, the upper one at height 5 with a square obstacle in it.
+    fn navmesh() -> PolyNavMesh {
+        let mut upper = testing::layer(
+            (10.0, 10.0),
+            &[(
+                AffectorKind::Obstacle,
+                testing::rectangle((-1.0, -1.0), (1.0, 1.0)),
+            )],
+        );
+        upper.layer = NavMeshLayer {
+            floor: 5.0,
+            ceiling: f32::INFINITY,
+        };
+        let ground = testing::layer((10.0, 10.0), &[]);
+        testing::navmesh((10.0, 10.0), vec![ground, upper])
+    }
+
+    /// The lines of `obj` in each group, keyed by the group name.
+    fn groups(obj: &str) -> Vec<(&str, Vec<&str>)> {
+        let mut groups: Vec<(&str, Vec<&str>)> = vec![("", Vec::new())];
+        for line in obj.lines() {
+            match line.strip_prefix("g ") {
+                Some(name) => groups.push((name, Vec::new())),
+                None => groups.last_mut().unwrap().1.push(line),
+            }
+        }
+        groups
+    }
+
+    /// The vertex indices of each `f` or `l` line, which all have to be in bounds.
+    fn elements(lines: &[&str], vertex_count: usize) -> Vec<Vec<usize>> {
+        lines
+            .iter()
+            .filter(|line| !line.starts_with("v "))
+            .map(|line| {
+                let indices: Vec<usize> = line
+                    .split_whitespace()
+                    .skip(1)
+                    .map(|index| index.parse().unwrap())
+                    .collect();
+                assert!(indices
+                    .iter()
+                    .all(|&index| (1..=vertex_count).contains(&index)));
+                indices
+            })
+            .collect()
+    }
+
+    #[test]
+    fn triangulations_are_written_per_layer() {
+        let navmesh = navmesh();
+        let obj = cdt_to_obj(&navmesh);
+        let vertex_count = obj.lines().filter(|line| line.starts_with("v ")).count();
+        assert_eq!(
+            vertex_count,
+            navmesh
+                .layers
+                .iter()
+                .map(|layer| layer.cdt.num_vertices())
+                .sum::<usize>()
+        );
+
+        let groups = groups(&obj);
+        let names: Vec<_> = groups.iter().skip(1).map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [
+                "layer0_navigable",
+                "layer0_blocked",
+                "layer0_constraints",
+                "layer1_navigable",
+                "layer1_blocked",
+                "layer1_constraints",
+            ]
+        );
+        for (i, layer) in navmesh.layers.iter().enumerate() {
+            let [navigable, blocked, constraints] =
+                [1, 2, 3].map(|group| elements(&groups[i * 3 + group].1, vertex_count));
+            assert_eq!(navigable.len(), layer.navigable_faces.len());
+            assert_eq!(navigable.len() + blocked.len(), layer.cdt.num_inner_faces());
+            assert!(navigable.iter().chain(&blocked).all(|face| face.len() == 3));
+            assert_eq!(constraints.len(), layer.cdt.num_constraints());
+        }
+        // Only the upper layer has an obstacle in it.
+        assert!(groups[2].1.is_empty());
+        assert!(!groups[5].1.is_empty());
+
+        // The upper layer is at its floor, the lower one at 0.
+        let heights: Vec<f32> = obj
+            .lines()
+            .filter_map(|line| line.strip_prefix("v "))
+            .map(|line| line.split_whitespace().nth(1).unwrap().parse().unwrap())
+            .collect();
+        let ground_vertices = navmesh.layers[0].cdt.num_vertices();
+        assert!(heights[..ground_vertices]
+            .iter()
+            .all(|&height| height == 0.0));
+        assert!(heights[ground_vertices..]
+            .iter()
+            .all(|&height| height == 5.0));
+    }
+
+    #[test]
+    fn polygons_are_written_per_layer() {
+        let navmesh = navmesh();
+        let obj = polygons_to_obj(&navmesh);
+        let vertex_count = obj.lines().filter(|line| line.starts_with("v ")).count();
+        let meshes = &navmesh.meshes;
+        assert_eq!(
+            vertex_count,
+            meshes.iter().map(|mesh| mesh.vertices.len()).sum::<usize>()
+        );
+
+        let groups = groups(&obj);
+        let names: Vec<_> = groups.iter().skip(1).map(|(name, _)| *name).collect();
+        assert_eq!(names, ["layer0_polygons", "layer1_polygons"]);
+        let mut offset = 1;
+        for ((_, lines), mesh) in groups.iter().skip(1).zip(meshes) {
+            let polygons = elements(lines, vertex_count);
+            assert_eq!(polygons.len(), mesh.polygons.len());
+            // Each layer's polygons only use its own vertices.
+            for (polygon, expected) in polygons.iter().zip(&mesh.polygons) {
+                let expected: Vec<_> = expected.vertices.iter().map(|v| v + offset).collect();
+                assert_eq!(*polygon, expected);
+            }
+            offset += mesh.vertices.len();
+        }
+    }
+
+    #[test]
+    fn layers_are_drawn_top_down() {
+        let navmesh = navmesh();
+        assert!(layer_to_svg(&navmesh, 2).is_none());
+
+        let svg = layer_to_svg(&navmesh, 1).unwrap();
+        assert!(
+            svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-6 -6 12 12">"#)
+        );
+        assert!(svg.ends_with("</svg>\n"));
+
+        let layer = &navmesh.layers[1];
+        let fills = |colour: &str| svg.matches(&format!(r#"fill="{}"/>"#, colour)).count();
+        assert_eq!(fills(NAVIGABLE_COLOUR), layer.navigable_faces.len());
+        assert_eq!(
+            fills(BLOCKED_COLOUR),
+            layer.cdt.num_inner_faces() - layer.navigable_faces.len()
+        );
+        assert!(fills(BLOCKED_COLOUR) > 0);
+        assert_eq!(svg.matches("<line ").count(), layer.cdt.num_constraints());
+        // The faces and polygons are all drawn as polygons.
+        assert_eq!(
+            svg.matches("<polygon ").count(),
+            layer.cdt.num_inner_faces() + navmesh.meshes[1].polygons.len()
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! Builds a [`PolyNavMesh`] from a navmesh made elsewhere, such as a polyanya `.mesh` file.
+
+use std::{fmt, hash::Hasher, io, path::Path, str::SplitWhitespace};
+
+use bevy::prelude::*;
+use bevy_pathmesh::PathMesh;
+use spade::{ConstrainedDelaunayTriangulation, Point2, Triangulation};
+
+use crate::{
+    colliders::NavMeshPlane,
+    pathfinding::NavMeshGraph,
+    utils::{NavMeshRegions, StableHasher},
+    LayerMesh, NavMeshLayer, PolyNavMesh,
+};
+
+/// Why a navmesh couldn't be imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidMesh(pub String);
+
+impl fmt::Display for InvalidMesh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid navmesh: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidMesh {}
+
+impl PolyNavMesh {
+    /// Uses `mesh` as the navmesh instead of generating one, as a single layer covering every
+    /// height. Path queries on the [`PathMesh`] use `mesh` as is.
+    ///
+    /// Affectors are still applied on top of it as they change, but a [`RebuildNavMesh`](crate::RebuildNavMesh)
+    /// replaces it with a generated navmesh, so set [`NavMeshSettings::build_on_startup`](crate::NavMeshSettings::build_on_startup)
+    /// to false. Insert the returned navmesh as a resource.
+    ///
+    /// Meshes with vertices that aren't finite, polygons that use missing vertices or polygon edges
+    /// that cross are rejected.
+    pub fn from_polyanya_mesh(
+        mesh: polyanya::Mesh,
+        plane: NavMeshPlane,
+        pathmeshes: &mut Assets<PathMesh>,
+    ) -> Result<Self, InvalidMesh> {
+        let _span = info_span!("import_polyanya_mesh").entered();
+        check_mesh(&mesh)?;
+        let layers = vec![mesh_layer(&mesh)?];
+
+        let mut hasher = StableHasher::default();
+        // Written as a `u8` for the same reason as in `NavMeshSettings::inputs_hash`.
+        hasher.write_u8(plane as u8);
+        let mut min = Vec2::splat(f32::INFINITY);
+        let mut max = Vec2::splat(f32::NEG_INFINITY);
+        for vertex in &mesh.vertices {
+            hasher.write_u32(vertex.coords.x.to_bits());
+            hasher.write_u32(vertex.coords.y.to_bits());
+            min = min.min(vertex.coords);
+            max = max.max(vertex.coords);
+        }
+        for polygon in &mesh.polygons {
+            for &vertex in &polygon.vertices {
+                hasher.write_u64(vertex as u64);
+            }
+        }
+
+        let graph = NavMeshGraph::from_layers(
+            layers
+                .iter()
+                .map(|layer| (&layer.cdt, &layer.navigable_faces)),
+        );
+        if mesh.vertices.is_empty() {
+            (min, max) = (Vec2::ZERO, Vec2::ZERO);
+        }
+        let size = max - min;
+        let pathmesh = PathMesh::from_polyanya_mesh(mesh);
+        let meshes = vec![pathmesh.get()];
+        Ok(Self {
+            layers,
+            graph,
+            inputs_hash: hasher.finish(),
+            min_island_area: 0.0,
+            navmesh_handles: vec![pathmeshes.add(pathmesh)],
+            meshes,
+            dimensions: (size.x, size.y),
+            origin: (min.x, min.y),
+            plane,
+        })
+    }
+
+    /// Loads a navmesh from a file in polyanya's `.mesh` format. See [`Self::from_polyanya_mesh`].
+    ///
+    /// Files that aren't well formed, or whose mesh [`Self::from_polyanya_mesh`] rejects, fail with
+    /// [`io::ErrorKind::InvalidData`].
+    pub fn from_mesh_file(
+        path: impl AsRef<Path>,
+        plane: NavMeshPlane,
+        pathmeshes: &mut Assets<PathMesh>,
+    ) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let invalid = |error: String| io::Error::new(io::ErrorKind::InvalidData, error);
+        let text = std::str::from_utf8(&bytes).map_err(|error| invalid(error.to_string()))?;
+        check_mesh_file(text).map_err(invalid)?;
+        let mesh = polyanya::PolyanyaFile::from_bytes(&bytes).into();
+        Self::from_polyanya_mesh(mesh, plane, pathmeshes).map_err(|error| invalid(error.0))
+    }
+}
+
+/// Checks the layout of a `.mesh` file: a `mesh` header, version 2, the vertex and polygon counts,
+/// then a line per vertex with its position and polygons, and a line per polygon with its vertices
+/// and neighbours. Polygon indices are -1 outside of the mesh.
+fn check_mesh_file(text: &str) -> Result<(), String> {
+    let mut lines = text.lines().map(str::split_whitespace);
+    let mut next_line = |what: &str| lines.next().ok_or(format!("missing {}", what));
+
+    if next_line("header")?.collect::<Vec<_>>() != ["mesh"] {
+        return Err("not a polyanya mesh file".to_string());
+    }
+    if next_line("version")?.collect::<Vec<_>>() != ["2"] {
+        return Err("only version 2 mesh files are supported".to_string());
+    }
+    let mut counts = next_line("vertex and polygon counts")?;
+    let vertex_count: usize = parse(&mut counts, "vertex count")?;
+    let polygon_count: usize = parse(&mut counts, "polygon count")?;
+
+    let check_polygons = |values: &mut SplitWhitespace, count: usize| -> Result<(), String> {
+        for _ in 0..count {
+            let polygon: isize = parse(values, "polygon")?;
+            if polygon < -1 || polygon >= polygon_count as isize {
+                return Err(format!("polygon {} doesn't exist", polygon));
+            }
+        }
+        Ok(())
+    };
+    for vertex in 0..vertex_count {
+        let mut values = next_line(&format!("vertex {}", vertex))?;
+        parse::<f32>(&mut values, "x")?;
+        parse::<f32>(&mut values, "y")?;
+        let count = parse(&mut values, "polygon count")?;
+        check_polygons(&mut values, count)
+            .map_err(|error| format!("vertex {}: {}", vertex, error))?;
+    }
+    for polygon in 0..polygon_count {
+        let mut values = next_line(&format!("polygon {}", polygon))?;
+        let count: usize = parse(&mut values, "vertex count")?;
+        if count < 3 {
+            return Err(format!("polygon {} has fewer than 3 vertices", polygon));
+        }
+        for _ in 0..count {
+            let vertex: usize = parse(&mut values, "vertex")?;
+            if vertex >= vertex_count {
+                return Err(format!(
+                    "polygon {} uses missing vertex {}",
+                    polygon, vertex
+                ));
+            }
+        }
+        check_polygons(&mut values, count)
+            .map_err(|error| format!("polygon {}: {}", polygon, error))?;
+    }
+    Ok(())
+}
+
+/// Cuts the polygons' edges into a triangulation so the navmesh can still be updated and drawn,
+/// with each polygon marked as walkable. `mesh` must have passed [`check_mesh`].
+fn mesh_layer(mesh: &polyanya::Mesh) -> Result<LayerMesh, InvalidMesh> {
+    let mut cdt = ConstrainedDelaunayTriangulation::<Point2<f32>>::new();
+    let handles = mesh
+        .vertices
+        .iter()
+        .map(|vertex| cdt.insert(Point2::new(vertex.coords.x, vertex.coords.y)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| InvalidMesh(format!("{:?}", error)))?;
+
+    let mut regions = NavMeshRegions::default();
+    for (i, polygon) in mesh.polygons.iter().enumerate() {
+        let mut previous = polygon.vertices[polygon.vertices.len() - 1];
+        for &vertex in &polygon.vertices {
+            let (from, to) = (handles[previous], handles[vertex]);
+            if !cdt.can_add_constraint(from, to) {
+                return Err(InvalidMesh(format!(
+                    "the edge of polygon {} from vertex {} to {} crosses another polygon",
+                    i, previous, vertex
+                )));
+            }
+            cdt.add_constraint(from, to);
+            previous = vertex;
+        }
+        regions.walkable.push(
+            polygon
+                .vertices
+                .iter()
+                .map(|&vertex| cdt.vertex(handles[vertex]).position())
+                .collect(),
+        );
+    }
+
+    Ok(LayerMesh::new(cdt, regions, NavMeshLayer::default()))
+}
+
+/// The checks on a mesh's structure that don't need its triangulation.
+fn check_mesh(mesh: &polyanya::Mesh) -> Result<(), InvalidMesh> {
+    for (i, vertex) in mesh.vertices.iter().enumerate() {
+        if !vertex.coords.is_finite() {
+            return Err(InvalidMesh(format!("vertex {} isn't finite", i)));
+        }
+        if let Some(polygon) = vertex
+            .polygons
+            .iter()
+            .find(|&&polygon| polygon < -1 || polygon >= mesh.polygons.len() as isize)
+        {
+            return Err(InvalidMesh(format!(
+                "vertex {} is next to missing polygon {}",
+                i, polygon
+            )));
+        }
+    }
+    for (i, polygon) in mesh.polygons.iter().enumerate() {
+        if polygon.vertices.len() < 3 {
+            return Err(InvalidMesh(format!(
+                "polygon {} has fewer than 3 vertices",
+                i
+            )));
+        }
+        if let Some(vertex) = polygon
+            .vertices
+            .iter()
+            .find(|&&vertex| vertex >= mesh.vertices.len())
+        {
+            return Err(InvalidMesh(format!(
+                "polygon {} uses missing vertex {}",
+                i, vertex
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn parse<T: std::str::FromStr>(values: &mut SplitWhitespace, what: &str) -> Result<T, String> {
+    let value = values.next().ok_or(format!("missing {}", what))?;
+    value
+        .parse()
+        .map_err(|_| format!("{} is not a valid {}", value, what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "mesh
+2
+4 1
+0 0 1 0
+1 0 1 0
+1 1 1 0
+0 1 1 0
+4 0 1 2 3 -1 -1 -1 -1
+";
+
+    #[test]
+    fn well_formed_mesh_files_pass() {
+        assert_eq!(check_mesh_file(SQUARE), Ok(()));
+    }
+
+    fn mesh(text: &str) -> polyanya::Mesh {
+        polyanya::PolyanyaFile::from_bytes(text.as_bytes()).into()
+    }
+
+    #[test]
+    fn polygons_are_cut_into_the_triangulation() {
+        let mesh = mesh(SQUARE);
+        assert_eq!(check_mesh(&mesh), Ok(()));
+        let layer = mesh_layer(&mesh).unwrap();
+        assert_eq!(layer.cdt.num_vertices(), 4);
+        assert_eq!(layer.cdt.num_constraints(), 4);
+        assert_eq!(layer.regions.walkable.len(), 1);
+    }
+
+    #[test]
+    fn broken_meshes_are_rejected() {
+        let mut not_finite = mesh(SQUARE);
+        not_finite.vertices[2].coords.x = f32::NAN;
+        assert!(check_mesh(&not_finite).is_err());
+
+        let mut missing_vertex = mesh(SQUARE);
+        missing_vertex.polygons[0].vertices[3] = 7;
+        assert!(check_mesh(&missing_vertex).is_err());
+
+        let mut missing_polygon = mesh(SQUARE);
+        missing_polygon.vertices[0].polygons[0] = 3;
+        assert!(check_mesh(&missing_polygon).is_err());
+
+        // Two squares that overlap, so their edges cross.
+        let crossing = mesh(
+            "mesh
+2
+8 2
+0 0 1 0
+2 0 1 0
+2 2 1 0
+0 2 1 0
+1 1 1 1
+3 1 1 1
+3 3 1 1
+1 3 1 1
+4 0 1 2 3 -1 -1 -1 -1
+4 4 5 6 7 -1 -1 -1 -1
+",
+        );
+        assert_eq!(check_mesh(&crossing), Ok(()));
+        assert!(mesh_layer(&crossing).is_err());
+    }
+
+    #[test]
+    fn malformed_mesh_files_are_rejected() {
+        // Cut off after the vertices.
+        let truncated = SQUARE.lines().take(7).collect::<Vec<_>>().join("\n");
+        assert!(check_mesh_file(&truncated).is_err());
+        assert!(check_mesh_file(&SQUARE.replace("4 0 1 2 3", "4 0 1 2 9")).is_err());
+        assert!(check_mesh_file(&SQUARE.replace("1 1 1 0", "1 one 1 0")).is_err());
+        assert!(check_mesh_file(&SQUARE.replace("1 1 1 0", "1 1 1 5")).is_err());
+        assert!(check_mesh_file("").is_err());
+    }
+}
@@ -1,20 +1,50 @@
 use core::panic;
-use std::{marker::PhantomData, sync::Arc};
+use std::{hash::Hasher, marker::PhantomData, sync::Arc, time::Duration};
 
-use bevy::prelude::*;
+use bevy::{
+    ecs::query::Has,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::{HashMap, Instant},
+};
 use bevy_pathmesh::PathMesh;
 pub use colliders::PolyanyaCollider;
-use parry3d::shape::{HeightField, SharedShape, TypedShape};
+#[cfg(feature = "2d")]
+pub use colliders::PolyanyaCollider2d;
+use colliders::{ColliderFootprint, ConvexSolid, Footprint, NavMeshPlane, SolidMesh, XzFootprint};
+use futures_lite::future;
+pub use import::InvalidMesh;
+use parry3d::shape::{Cone, Cuboid, Cylinder, HeightField, SharedShape, TypedShape};
+use pathfinding::{AreaMask, NavMeshGraph, NavPath, OffMeshLink};
 use spade::{ConstrainedDelaunayTriangulation, Point2, Triangulation};
-use utils::{compute_navigable_faces, NavMeshAffector};
+use utils::{AffectorKind, NavMeshAffector, NavMeshArea, NavMeshRegions, NavMeshWalkable};
+use validate::validate_mesh;
 
-use crate::utils::into_polyanya_mesh;
+#[cfg(feature = "2d")]
+use crate::utils::{capsule_points, circle_points};
+use crate::utils::{
+    compute_navigable_faces, heightfield_height_at, navigable_trimesh_where, polyline_chains,
+    thicken_polyline, StableHasher,
+};
 
+#[cfg(feature = "serialize")]
+pub mod bake;
 pub mod colliders;
+pub mod debug;
+pub mod diagnostics;
+pub mod export;
+mod import;
+pub mod obstacle;
+pub mod pathfinding;
+#[cfg(test)]
+mod testing;
 pub mod utils;
+pub mod validate;
 
+/// Builds the navmesh on the XZ plane. Adding several of these for different collider types
+/// shares one navmesh, using the settings of whichever was added first.
 pub struct PolyanyaNavMeshPlugin<ColliderComponent> {
-    _settings: NavMeshSettings,
+    settings: NavMeshSettings,
     _collider_type: PhantomData<ColliderComponent>,
 }
 
@@ -23,14 +53,364 @@ where
     C: PolyanyaCollider,
 {
     fn build(&self, app: &mut App) {
-        app.insert_resource(DrawCdt(true))
-            .add_systems(PostStartup, gen_navmesh::<C>)
-            .add_systems(Update, draw_cdt::<C>)
-            .add_systems(PreUpdate, update_navmesh::<C>);
+        add_navmesh_core(app, &self.settings, NavMeshPlane::Xz);
+        add_navmesh_collider::<XzFootprint<C>>(app);
     }
 }
 
-pub struct NavMeshSettings {}
+/// Adds another collider type's affectors to the navmesh built by [`PolyanyaNavMeshPlugin`].
+pub struct NavMeshColliderPlugin<ColliderComponent>(PhantomData<ColliderComponent>);
+
+impl<C> Default for NavMeshColliderPlugin<C> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<C> Plugin for NavMeshColliderPlugin<C>
+where
+    C: PolyanyaCollider,
+{
+    fn build(&self, app: &mut App) {
+        add_navmesh_collider::<XzFootprint<C>>(app);
+    }
+}
+
+/// Builds the navmesh on the XY plane from 2D colliders. Bounded by [`NavMeshBounds2d`] instead
+/// of the [`NavHeightField`].
+#[cfg(feature = "2d")]
+pub struct PolyanyaNavMesh2dPlugin<ColliderComponent> {
+    settings: NavMeshSettings,
+    _collider_type: PhantomData<ColliderComponent>,
+}
+
+#[cfg(feature = "2d")]
+impl<C> Plugin for PolyanyaNavMesh2dPlugin<C>
+where
+    C: PolyanyaCollider2d,
+{
+    fn build(&self, app: &mut App) {
+        add_navmesh_core(app, &self.settings, NavMeshPlane::Xy);
+        add_navmesh_collider::<colliders::XyFootprint<C>>(app);
+    }
+}
+
+/// Adds another 2D collider type's affectors to the navmesh built by [`PolyanyaNavMesh2dPlugin`].
+#[cfg(feature = "2d")]
+pub struct NavMeshCollider2dPlugin<ColliderComponent>(PhantomData<ColliderComponent>);
+
+#[cfg(feature = "2d")]
+impl<C> Default for NavMeshCollider2dPlugin<C> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[cfg(feature = "2d")]
+impl<C> Plugin for NavMeshCollider2dPlugin<C>
+where
+    C: PolyanyaCollider2d,
+{
+    fn build(&self, app: &mut App) {
+        add_navmesh_collider::<colliders::XyFootprint<C>>(app);
+    }
+}
+
+#[cfg(feature = "2d")]
+impl<C> PolyanyaNavMesh2dPlugin<C>
+where
+    C: PolyanyaCollider2d,
+{
+    pub fn new(settings: NavMeshSettings) -> Self {
+        Self {
+            settings,
+            _collider_type: PhantomData,
+        }
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NavMeshSet {
+    /// Applies finished builds and reads navmesh events.
+    Poll,
+    /// Gathers affector footprints from every registered collider type.
+    CollectFootprints,
+    /// Starts a new build if one is due.
+    Build,
+}
+
+/// Sets up the single navmesh pipeline shared by every registered collider type.
+fn add_navmesh_core(app: &mut App, settings: &NavMeshSettings, plane: NavMeshPlane) {
+    if let Some(existing_plane) = app.world.get_resource::<NavMeshPlane>() {
+        if *existing_plane != plane {
+            warn!(
+                "Navmesh plugins for both the {:?} and {:?} planes were added, using {:?}.",
+                existing_plane, plane, existing_plane
+            );
+        }
+        return;
+    }
+
+    app.insert_resource(settings.clone())
+        .insert_resource(plane)
+        .add_event::<ApplyNavMeshUpdates>()
+        .add_event::<RebuildNavMesh>()
+        .add_event::<NavMeshGenerated>()
+        .add_event::<NavMeshUpdated>()
+        .init_resource::<NavMeshStatus>()
+        .init_resource::<NavMeshTask>()
+        .init_resource::<PendingFootprints>()
+        .configure_sets(
+            PreUpdate,
+            (
+                NavMeshSet::Poll,
+                NavMeshSet::CollectFootprints,
+                NavMeshSet::Build,
+            )
+                .chain(),
+        )
+        .add_systems(PostStartup, request_initial_navmesh)
+        .add_systems(
+            PreUpdate,
+            (
+                (
+                    poll_navmesh_task,
+                    read_navmesh_events,
+                    update_off_mesh_links,
+                )
+                    .chain()
+                    .in_set(NavMeshSet::Poll),
+                (gen_navmesh, update_navmesh)
+                    .chain()
+                    .in_set(NavMeshSet::Build),
+            ),
+        );
+}
+
+pub(crate) fn add_navmesh_collider<F: ColliderFootprint>(app: &mut App) {
+    app.add_systems(
+        PreUpdate,
+        collect_footprints::<F>.in_set(NavMeshSet::CollectFootprints),
+    )
+    .add_systems(
+        Update,
+        debug::draw_footprints::<F>.run_if(resource_exists::<debug::NavMeshDebugSettings>()),
+    );
+}
+
+#[derive(Resource, Clone)]
+pub struct NavMeshSettings {
+    pub update_policy: NavMeshUpdatePolicy,
+    /// Width given to open affectors such as fences and thin walls.
+    pub wall_thickness: f32,
+    /// Open affectors are widened by this much on each side so agents keep their distance.
+    pub agent_radius: f32,
+    /// Obstacles only cut the navmesh where they overlap the band from the ground, sampled from
+    /// the [`NavHeightField`], up to this height. Anything overhead or underground is ignored.
+    pub agent_height: f32,
+    /// Send a [`RebuildNavMesh`] in `PostStartup`. Disable this to decide when the first build happens.
+    pub build_on_startup: bool,
+    /// Height ranges that each get their own navmesh, lowest first, for bridges and multi-storey
+    /// buildings. Connect them with [`OffMeshLink`]s at stairs and ramps.
+    ///
+    /// Only the first layer is walkable everywhere. The others are only walkable inside
+    /// [`NavMeshWalkable`] affectors, so give bridge decks and floors a [`NavMeshWalkable`] or they
+    /// would block their own layer instead.
+    pub layers: Vec<NavMeshLayer>,
+    /// Islands of connected polygons with less surface area than this are left out of the
+    /// [`PathMesh`], so agents aren't placed on scraps walled off by obstacles. They are still
+    /// part of the [`NavMeshGraph`].
+    ///
+    /// Islands are measured before [`OffMeshLink`]s are resolved, so a small island that is only
+    /// reachable through a link is left out all the same. Only the [`NavMeshGraph`] can path to it.
+    pub min_island_area: f32,
+    /// Checks every build with [`validate_mesh`] and logs the problems it finds. On by default in
+    /// debug builds.
+    pub validate_builds: bool,
+}
+
+impl NavMeshSettings {
+    fn wall_half_width(&self) -> f32 {
+        self.wall_thickness * 0.5 + self.agent_radius
+    }
+
+    fn build_context(
+        &self,
+        dimensions: (f32, f32),
+        heightfield: Option<&NavHeightField>,
+        inputs_hash: u64,
+    ) -> BuildContext {
+        BuildContext {
+            dimensions,
+            wall_half_width: self.wall_half_width(),
+            agent_height: self.agent_height,
+            heightfield: heightfield.map(|heightfield| heightfield.heightfield.clone()),
+            inputs_hash,
+            min_island_area: self.min_island_area,
+            validate: self.validate_builds,
+        }
+    }
+
+    /// Hashes everything other than the affectors that changes the generated navmesh.
+    fn inputs_hash(
+        &self,
+        dimensions: (f32, f32),
+        plane: NavMeshPlane,
+        heightfield: Option<&NavHeightField>,
+    ) -> u64 {
+        let mut hasher = StableHasher::default();
+        // The derived `Hash` writes an `isize`, whose size depends on the platform.
+        hasher.write_u8(plane as u8);
+        for value in [
+            dimensions.0,
+            dimensions.1,
+            self.wall_thickness,
+            self.agent_radius,
+            self.agent_height,
+            self.min_island_area,
+        ] {
+            hasher.write_u32(value.to_bits());
+        }
+        for layer in &self.layers {
+            hasher.write_u32(layer.floor.to_bits());
+            hasher.write_u32(layer.ceiling.to_bits());
+        }
+        // The ground decides which obstacles are ignored and where solids are sliced.
+        if let Some(heightfield) = heightfield {
+            let heightfield = &heightfield.heightfield;
+            let scale = heightfield.scale();
+            for value in [scale.x, scale.y, scale.z]
+                .into_iter()
+                .chain(heightfield.heights().iter().copied())
+            {
+                hasher.write_u32(value.to_bits());
+            }
+        }
+        hasher.finish()
+    }
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        Self {
+            update_policy: NavMeshUpdatePolicy::default(),
+            wall_thickness: 0.2,
+            agent_radius: 0.0,
+            agent_height: 2.0,
+            build_on_startup: true,
+            layers: vec![NavMeshLayer::default()],
+            min_island_area: 0.0,
+            validate_builds: cfg!(debug_assertions),
+        }
+    }
+}
+
+/// A range of heights along the plane's normal that gets its own navmesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct NavMeshLayer {
+    pub floor: f32,
+    pub ceiling: f32,
+}
+
+impl Default for NavMeshLayer {
+    /// Covers every height.
+    fn default() -> Self {
+        Self {
+            floor: f32::NEG_INFINITY,
+            ceiling: f32::INFINITY,
+        }
+    }
+}
+
+impl NavMeshLayer {
+    pub fn contains_height(&self, height: f32) -> bool {
+        self.floor <= height && height < self.ceiling
+    }
+
+    /// The heights an agent standing on this layer occupies, from the ground or the layer's floor,
+    /// whichever is higher.
+    fn agent_band(&self, ground: Option<f32>, agent_height: f32) -> (f32, f32) {
+        let bottom = ground.map_or(self.floor, |ground| ground.max(self.floor));
+        let top = if bottom.is_finite() {
+            (bottom + agent_height).min(self.ceiling)
+        } else {
+            self.ceiling
+        };
+        (bottom, top)
+    }
+
+    /// The heights `kind` is sliced between on this layer. Only the slice of an obstacle the agent
+    /// would bump into is cut out, while walkable ground and areas use their whole outline.
+    fn slice_band(&self, kind: AffectorKind, ground: Option<f32>, agent_height: f32) -> (f32, f32) {
+        match kind {
+            AffectorKind::Obstacle => self.agent_band(ground, agent_height),
+            AffectorKind::Walkable | AffectorKind::Area(_) => (f32::NEG_INFINITY, f32::INFINITY),
+        }
+    }
+
+    /// Obstacles cut a layer where they overlap its agent band, while walkable ground and areas
+    /// belong to the layer their top is in.
+    fn includes(&self, footprint: &AffectorFootprint, (bottom, top): (f32, f32)) -> bool {
+        let Some((min, max)) = footprint.vertical_extent else {
+            return true;
+        };
+        match footprint.kind {
+            AffectorKind::Obstacle => min < top && max >= bottom,
+            AffectorKind::Walkable | AffectorKind::Area(_) => self.contains_height(max),
+        }
+    }
+}
+
+/// Controls when affectors added since the last build are batched into a rebuild.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NavMeshUpdatePolicy {
+    /// Rebuild on the first frame that has pending changes.
+    #[default]
+    Immediate,
+    /// Rebuild once changes have been pending for this many frames.
+    EveryNFrames(u32),
+    /// Rebuild once no affectors have been added for this long.
+    AfterQuietPeriod(Duration),
+    /// Only rebuild when an [`ApplyNavMeshUpdates`] event is sent.
+    Manual,
+}
+
+/// Rebuilds the navmesh with all pending changes, regardless of the [`NavMeshUpdatePolicy`].
+#[derive(Event, Default)]
+pub struct ApplyNavMeshUpdates;
+
+/// Discards the current navmesh and regenerates it from the heightfield and every affector.
+#[derive(Event, Default)]
+pub struct RebuildNavMesh;
+
+/// Sent when a [`RebuildNavMesh`] has finished.
+#[derive(Event, Clone, Debug)]
+pub struct NavMeshGenerated {
+    /// One per layer, lowest first.
+    pub navmesh_handles: Vec<Handle<PathMesh>>,
+    pub stats: NavMeshBuildStats,
+}
+
+/// Sent when newly added affectors have been cut into the navmesh.
+#[derive(Event, Clone, Debug)]
+pub struct NavMeshUpdated {
+    /// One per layer, lowest first.
+    pub navmesh_handles: Vec<Handle<PathMesh>>,
+    pub stats: NavMeshBuildStats,
+    /// The affectors that were cut in.
+    pub affectors: Vec<Entity>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NavMeshBuildStats {
+    /// Time spent on the background task, not including time spent waiting to be polled.
+    pub build_time: Duration,
+    pub affectors: usize,
+    pub vertices: usize,
+    pub polygons: usize,
+    pub constraints: usize,
+}
 
 #[derive(Component)]
 pub struct MyCollider {
@@ -49,9 +429,226 @@ impl PolyanyaCollider for MyCollider {
 
 #[derive(Resource)]
 pub struct PolyNavMesh {
-    cdt: ConstrainedDelaunayTriangulation<Point2<f32>>,
-    pub navmesh_handle: Handle<PathMesh>,
+    layers: Vec<LayerMesh>,
+    graph: NavMeshGraph,
+    /// Combined hash of the settings and every affector the navmesh was built from.
+    inputs_hash: u64,
+    /// Islands smaller than this were left out of the [`PathMesh`]es.
+    min_island_area: f32,
+    /// One per layer, lowest first.
+    pub navmesh_handles: Vec<Handle<PathMesh>>,
+    /// The polyanya mesh of each [`PathMesh`], so it can be exported and checked without them.
+    meshes: Vec<Arc<polyanya::Mesh>>,
     pub dimensions: (f32, f32),
+    /// The corner of the navmesh with the lowest coordinates. Generated navmeshes are centred on
+    /// zero, imported ones can be anywhere.
+    pub origin: (f32, f32),
+    pub plane: NavMeshPlane,
+}
+
+#[derive(Clone)]
+struct LayerMesh {
+    cdt: ConstrainedDelaunayTriangulation<Point2<f32>>,
+    regions: NavMeshRegions,
+    layer: NavMeshLayer,
+    /// The faces of `cdt` in `regions` that can be walked on, with their area. Kept up to date
+    /// with [`Self::update_navigable_faces`] whenever affectors are added.
+    navigable_faces: HashMap<usize, NavMeshArea>,
+}
+
+impl LayerMesh {
+    fn new(
+        cdt: ConstrainedDelaunayTriangulation<Point2<f32>>,
+        regions: NavMeshRegions,
+        layer: NavMeshLayer,
+    ) -> Self {
+        let mut layer = Self {
+            cdt,
+            regions,
+            layer,
+            navigable_faces: HashMap::default(),
+        };
+        layer.update_navigable_faces();
+        layer
+    }
+
+    fn update_navigable_faces(&mut self) {
+        self.navigable_faces = compute_navigable_faces(&self.cdt, &self.regions);
+    }
+}
+
+impl PolyNavMesh {
+    /// Changes whenever an affector is added or a setting that affects the navmesh changes, so it
+    /// can be used to tell whether a baked navmesh is stale.
+    pub fn inputs_hash(&self) -> u64 {
+        self.inputs_hash
+    }
+
+    /// The polyanya mesh path queries on the layer's [`PathMesh`] use, without the islands
+    /// smaller than [`NavMeshSettings::min_island_area`].
+    pub fn polyanya_mesh(&self, layer: usize) -> Option<&polyanya::Mesh> {
+        self.meshes.get(layer).map(|mesh| mesh.as_ref())
+    }
+
+    /// The lowest layer whose height range contains `height`.
+    pub fn layer_at(&self, height: f32) -> Option<usize> {
+        self.layers
+            .iter()
+            .position(|layer| layer.layer.contains_height(height))
+    }
+
+    /// Like [`Self::find_path_filtered`], between world space points that may be on different layers.
+    pub fn find_path_3d(&self, from: Vec3, to: Vec3, mask: AreaMask) -> Option<NavPath> {
+        let from_layer = self.layer_at(self.plane.height(from))?;
+        let to_layer = self.layer_at(self.plane.height(to))?;
+        self.graph.find_path_between_layers(
+            (from_layer, self.plane.to_plane(from)),
+            (to_layer, self.plane.to_plane(to)),
+            mask,
+        )
+    }
+
+    /// Searches for a path in plane coordinates that takes [`NavMeshArea`] costs into account,
+    /// unlike the [`PathMesh`] which only finds the shortest one.
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Option<NavPath> {
+        self.graph.find_path(from, to)
+    }
+
+    /// Like [`Self::find_path`], but only through areas allowed by `mask`.
+    pub fn find_path_filtered(&self, from: Vec2, to: Vec2, mask: AreaMask) -> Option<NavPath> {
+        self.graph.find_path_filtered(from, to, mask)
+    }
+
+    /// Quickly rules out impossible path queries. See [`NavMeshGraph::is_reachable_between_layers`].
+    pub fn is_reachable(&self, from: Vec2, to: Vec2) -> bool {
+        self.graph.is_reachable(from, to)
+    }
+
+    /// Like [`Self::is_reachable`], between world space points that may be on different layers.
+    pub fn is_reachable_3d(&self, from: Vec3, to: Vec3) -> bool {
+        let (Some(from_layer), Some(to_layer)) = (
+            self.layer_at(self.plane.height(from)),
+            self.layer_at(self.plane.height(to)),
+        ) else {
+            return false;
+        };
+        self.graph.is_reachable_between_layers(
+            (from_layer, self.plane.to_plane(from)),
+            (to_layer, self.plane.to_plane(to)),
+        )
+    }
+
+    pub fn graph(&self) -> &NavMeshGraph {
+        &self.graph
+    }
+}
+
+/// Whether a navmesh build is waiting to start, running on the [`AsyncComputeTaskPool`], or finished.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavMeshStatus {
+    #[default]
+    Pending,
+    Running,
+    Done,
+}
+
+/// The in-flight background build, if any. Only one build runs at a time.
+#[derive(Resource, Default)]
+struct NavMeshTask(Option<Task<GeneratedNavMesh>>);
+
+/// Footprints of affectors added since the last build was started.
+#[derive(Resource, Default)]
+struct PendingFootprints {
+    footprints: Vec<AffectorFootprint>,
+    /// Every affector's footprint, gathered on the frame a rebuild starts.
+    rebuild_footprints: Vec<AffectorFootprint>,
+    frames_waited: u32,
+    last_added: Duration,
+    apply_requested: bool,
+    rebuild_requested: bool,
+}
+
+/// What a background build needs from the main world.
+struct BuildContext {
+    dimensions: (f32, f32),
+    wall_half_width: f32,
+    agent_height: f32,
+    heightfield: Option<Arc<HeightField>>,
+    /// The hash of the navmesh being built on.
+    inputs_hash: u64,
+    min_island_area: f32,
+    validate: bool,
+}
+
+struct AffectorFootprint {
+    entity: Entity,
+    footprint: Footprint,
+    kind: AffectorKind,
+    vertical_extent: Option<(f32, f32)>,
+}
+
+impl AffectorFootprint {
+    /// Affector hashes are summed, so the order they're added in doesn't matter.
+    fn inputs_hash(&self) -> u64 {
+        let mut hasher = StableHasher::default();
+        match &self.footprint {
+            Footprint::Closed(points) | Footprint::Open(points) => {
+                for point in points {
+                    hasher.write_u32(point.x.to_bits());
+                    hasher.write_u32(point.y.to_bits());
+                }
+            }
+            Footprint::Solid(solid) => {
+                for (point, height) in &solid.vertices {
+                    hasher.write_u32(point.x.to_bits());
+                    hasher.write_u32(point.y.to_bits());
+                    hasher.write_u32(height.to_bits());
+                }
+            }
+            Footprint::Mesh(mesh) => {
+                for (point, height) in &mesh.vertices {
+                    hasher.write_u32(point.x.to_bits());
+                    hasher.write_u32(point.y.to_bits());
+                    hasher.write_u32(height.to_bits());
+                }
+                for index in mesh.triangles.iter().flatten() {
+                    hasher.write_u32(*index);
+                }
+            }
+        }
+        hasher.write_u8(match &self.footprint {
+            Footprint::Closed(_) => 0,
+            Footprint::Open(_) => 1,
+            Footprint::Solid(_) => 2,
+            Footprint::Mesh(_) => 3,
+        });
+        match self.kind {
+            AffectorKind::Obstacle => hasher.write_u8(0),
+            AffectorKind::Walkable => hasher.write_u8(1),
+            AffectorKind::Area(area) => {
+                hasher.write_u8(2);
+                hasher.write_u8(area.id);
+                hasher.write_u32(area.cost.to_bits());
+            }
+        }
+        if let Some((min, max)) = self.vertical_extent {
+            hasher.write_u32(min.to_bits());
+            hasher.write_u32(max.to_bits());
+        }
+        hasher.finish()
+    }
+}
+
+struct GeneratedNavMesh {
+    layers: Vec<LayerMesh>,
+    dimensions: (f32, f32),
+    inputs_hash: u64,
+    min_island_area: f32,
+    graph: NavMeshGraph,
+    pathmeshes: Vec<PathMesh>,
+    rebuild: bool,
+    stats: NavMeshBuildStats,
+    affectors: Vec<Entity>,
 }
 
 #[derive(Resource)]
@@ -59,101 +656,392 @@ pub struct NavHeightField {
     pub heightfield: Arc<HeightField>,
 }
 
+impl NavHeightField {
+    /// The height of the ground at `point` on the XZ plane, if it's within the heightfield.
+    pub fn height_at(&self, point: Vec2) -> Option<f32> {
+        heightfield_height_at(&self.heightfield, Point2::new(point.x, point.y))
+    }
+}
+
+/// Size of a 2D navmesh on the XY plane, centred on the origin.
+#[derive(Resource)]
+pub struct NavMeshBounds2d {
+    pub size: Vec2,
+}
+
 impl<C> PolyanyaNavMeshPlugin<C>
 where
     C: PolyanyaCollider,
 {
     pub fn new(settings: NavMeshSettings) -> Self {
         Self {
-            _settings: settings,
+            settings,
             _collider_type: PhantomData,
         }
     }
 }
 
-#[derive(Resource)]
-pub struct DrawCdt(pub bool);
+fn request_initial_navmesh(
+    settings: Res<NavMeshSettings>,
+    mut rebuild_events: EventWriter<RebuildNavMesh>,
+) {
+    if settings.build_on_startup {
+        rebuild_events.send_default();
+    }
+}
 
-fn draw_cdt<C: PolyanyaCollider>(
-    draw_cdt: Res<DrawCdt>,
-    cdt: Res<PolyNavMesh>,
-    mut gizmos: Gizmos,
-    collider_query: Query<(&C, &GlobalTransform), With<NavMeshAffector>>,
+fn read_navmesh_events(
+    mut apply_events: EventReader<ApplyNavMeshUpdates>,
+    mut rebuild_events: EventReader<RebuildNavMesh>,
+    mut pending: ResMut<PendingFootprints>,
 ) {
-    if draw_cdt.0 {
-        let navigable_faces = compute_navigable_faces(&cdt.cdt);
-        for face in cdt.cdt.inner_faces() {
-            if navigable_faces.contains(&face.index()) {
-                let vertices = face.vertices();
-                let a = vertices[0].position();
-                let b = vertices[1].position();
-                let c = vertices[2].position();
-                let a = Vec3::new(a.x, 2.0, a.y);
-                let b = Vec3::new(b.x, 2.0, b.y);
-                let c = Vec3::new(c.x, 2.0, c.y);
-                gizmos.line(a, b, Color::GREEN);
-                gizmos.line(b, c, Color::GREEN);
-                gizmos.line(c, a, Color::GREEN);
-            } else {
-                let vertices = face.vertices();
-                let a = vertices[0].position();
-                let b = vertices[1].position();
-                let c = vertices[2].position();
-                let a = Vec3::new(a.x, 2.1, a.y);
-                let b = Vec3::new(b.x, 2.1, b.y);
-                let c = Vec3::new(c.x, 2.1, c.y);
-                gizmos.line(a, b, Color::RED);
-                gizmos.line(b, c, Color::RED);
-                gizmos.line(c, a, Color::RED);
-            }
+    if apply_events.iter().count() > 0 {
+        pending.apply_requested = true;
+    }
+    if rebuild_events.iter().count() > 0 {
+        pending.rebuild_requested = true;
+    }
+}
+
+/// Re-resolves every [`OffMeshLink`] against the navmesh when a link or the navmesh changes,
+/// including when a navmesh is inserted directly, such as an imported or baked one.
+pub(crate) fn update_off_mesh_links(
+    navmesh: Option<ResMut<PolyNavMesh>>,
+    links: Query<(Entity, &OffMeshLink, &GlobalTransform)>,
+    changed: Query<
+        (),
+        (
+            With<OffMeshLink>,
+            Or<(Changed<OffMeshLink>, Changed<GlobalTransform>)>,
+        ),
+    >,
+    mut removed: RemovedComponents<OffMeshLink>,
+    mut generated_events: EventReader<NavMeshGenerated>,
+    mut updated_events: EventReader<NavMeshUpdated>,
+) {
+    let Some(mut navmesh) = navmesh else {
+        return;
+    };
+    let built =
+        navmesh.is_added() || generated_events.iter().count() + updated_events.iter().count() > 0;
+    let removed = removed.iter().count() > 0;
+    if !built && !removed && changed.is_empty() {
+        return;
+    }
+
+    let plane = navmesh.plane;
+    navmesh.graph.clear_links();
+    for (entity, link, transform) in links.iter() {
+        let start = transform.transform_point(link.start);
+        let end = transform.transform_point(link.end);
+        let layers = (
+            navmesh.layer_at(plane.height(start)),
+            navmesh.layer_at(plane.height(end)),
+        );
+        let added = match layers {
+            (Some(start_layer), Some(end_layer)) => navmesh.graph.add_link(
+                entity,
+                (start_layer, plane.to_plane(start)),
+                (end_layer, plane.to_plane(end)),
+                link,
+            ),
+            _ => false,
+        };
+        if !added {
+            debug!("off-mesh link {:?} has an end outside the navmesh", entity);
         }
+    }
+}
 
-        // for edge in cdt.cdt.undirected_edges() {
-        //     let vertices = edge.vertices();
-        //     let start_pos = vertices[0].position();
-        //     let end_pos = vertices[1].position();
-        //     // if let (Some(start_pos), Some(end_pos)) =
-        //     //     (vertices[0].position(), vertices[1].position())
-        //     // {
-        //     let colour = if edge.() {
-        //         Color::RED
-        //     } else {
-        //         Color::GREEN
-        //     };
-        //     // let colour = if edge.is_constraint_edge() {
-        //     //     Color::RED
-        //     // } else {
-        //     //     Color::GREEN
-        //     // };
-        //     let start = Vec3::new(start_pos.x, 2.0, start_pos.y);
-        //     let end = Vec3::new(end_pos.x, 2.0, end_pos.y);
-        //     gizmos.line(start, end, colour);
-        //     // }
-        // }
+// Footprints are snapshotted here as the collider shapes can't be sent to the task pool.
+fn collect_footprints<F: ColliderFootprint>(
+    added_query: Query<
+        (
+            Entity,
+            &F::Collider,
+            &GlobalTransform,
+            Has<NavMeshWalkable>,
+            Option<&NavMeshArea>,
+        ),
+        (With<NavMeshAffector>, Added<F::Collider>),
+    >,
+    collider_query: Query<
+        (
+            Entity,
+            &F::Collider,
+            &GlobalTransform,
+            Has<NavMeshWalkable>,
+            Option<&NavMeshArea>,
+        ),
+        With<NavMeshAffector>,
+    >,
+    plane: Res<NavMeshPlane>,
+    task: Res<NavMeshTask>,
+    time: Res<Time>,
+    mut pending: ResMut<PendingFootprints>,
+) {
+    let plane = *plane;
+    let _span = info_span!("collect_footprints").entered();
+    let to_footprints = |(entity, collider, transform, walkable, area): (
+        Entity,
+        &F::Collider,
+        &GlobalTransform,
+        bool,
+        Option<&NavMeshArea>,
+    )| {
+        if walkable && area.is_some() {
+            warn!(
+                "Affector {:?} is both NavMeshWalkable and a NavMeshArea, its area is ignored.",
+                entity
+            );
+        }
+        let kind = AffectorKind::new(walkable, area);
+        let vertical_extent = F::vertical_extent(collider, transform, plane);
+        F::footprint(collider, transform, plane)
+            .into_iter()
+            .map(move |footprint| AffectorFootprint {
+                entity,
+                footprint,
+                kind,
+                vertical_extent,
+            })
+    };
+
+    // `gen_navmesh` starts a rebuild this frame, which needs every affector.
+    if pending.rebuild_requested && task.0.is_none() {
+        let footprints = collider_query.iter().flat_map(to_footprints);
+        pending.rebuild_footprints.extend(footprints);
+        return;
+    }
+
+    for affector in added_query.iter() {
+        pending.footprints.extend(to_footprints(affector));
+        pending.last_added = time.elapsed();
     }
 }
 
 // TODO: Current design only allows for one navmesh.
-fn gen_navmesh<C: PolyanyaCollider>(
+#[allow(clippy::too_many_arguments)]
+fn gen_navmesh(
     mut commands: Commands,
-    collider_query: Query<(Entity, &C, &GlobalTransform), With<NavMeshAffector>>,
-    heightfield: Res<NavHeightField>,
+    plane: Res<NavMeshPlane>,
+    settings: Res<NavMeshSettings>,
+    heightfield: Option<Res<NavHeightField>>,
+    bounds_2d: Option<Res<NavMeshBounds2d>>,
+    navmesh: Option<Res<PolyNavMesh>>,
+    mut task: ResMut<NavMeshTask>,
+    mut pending: ResMut<PendingFootprints>,
+    mut status: ResMut<NavMeshStatus>,
+) {
+    // A running build is allowed to finish, the rebuild starts once it has been polled.
+    if !pending.rebuild_requested || task.0.is_some() {
+        return;
+    }
+    pending.rebuild_requested = false;
+    let footprints = std::mem::take(&mut pending.rebuild_footprints);
+
+    let dimensions = navmesh_dimensions(*plane, heightfield.as_deref(), bounds_2d.as_deref());
+    let Some(dimensions) = dimensions else {
+        warn!("Cannot rebuild the navmesh without a NavHeightField or NavMeshBounds2d resource.");
+        return;
+    };
+    if settings.layers.is_empty() {
+        warn!("Cannot rebuild the navmesh without any NavMeshSettings::layers.");
+        return;
+    }
+    let layers: Vec<LayerMesh> = settings
+        .layers
+        .iter()
+        .enumerate()
+        .map(|(i, &layer)| LayerMesh {
+            cdt: bounding_cdt(dimensions),
+            regions: NavMeshRegions {
+                walkable_only: i > 0,
+                ..default()
+            },
+            layer,
+            // Filled in by `build_navmesh` once the affectors are in.
+            navigable_faces: default(),
+        })
+        .collect();
+    let inputs_hash = settings.inputs_hash(dimensions, *plane, heightfield.as_deref());
+    let context = settings.build_context(dimensions, heightfield.as_deref(), inputs_hash);
+
+    // Every affector is part of the rebuild, including the ones waiting for an update.
+    pending.footprints.clear();
+    pending.frames_waited = 0;
+    pending.apply_requested = false;
+
+    // The current navmesh keeps being used until `poll_navmesh_task` swaps in the rebuilt one.
+    if navmesh.is_none() {
+        commands.insert_resource(PolyNavMesh {
+            layers: layers.clone(),
+            graph: NavMeshGraph::default(),
+            inputs_hash,
+            min_island_area: settings.min_island_area,
+            navmesh_handles: Vec::new(),
+            meshes: Vec::new(),
+            dimensions,
+            origin: (-dimensions.0 * 0.5, -dimensions.1 * 0.5),
+            plane: *plane,
+        });
+    }
+    task.0 = Some(
+        AsyncComputeTaskPool::get()
+            .spawn(async move { build_navmesh(layers, footprints, context, true) }),
+    );
+    *status = NavMeshStatus::Running;
+}
+
+fn update_navmesh(
+    navmesh: Option<Res<PolyNavMesh>>,
+    settings: Res<NavMeshSettings>,
+    heightfield: Option<Res<NavHeightField>>,
+    time: Res<Time>,
+    mut task: ResMut<NavMeshTask>,
+    mut pending: ResMut<PendingFootprints>,
+    mut status: ResMut<NavMeshStatus>,
+) {
+    let _span = info_span!("update_navmesh").entered();
+
+    // `gen_navmesh` picks up everything once a rebuild has been requested.
+    if pending.rebuild_requested {
+        if task.0.is_none() {
+            *status = NavMeshStatus::Pending;
+        }
+        return;
+    }
+
+    if pending.footprints.is_empty() {
+        pending.apply_requested = false;
+        return;
+    }
+    pending.frames_waited += 1;
+
+    let ready = pending.apply_requested
+        || match settings.update_policy {
+            NavMeshUpdatePolicy::Immediate => true,
+            NavMeshUpdatePolicy::EveryNFrames(frames) => pending.frames_waited >= frames,
+            NavMeshUpdatePolicy::AfterQuietPeriod(period) => {
+                time.elapsed() - pending.last_added >= period
+            }
+            NavMeshUpdatePolicy::Manual => false,
+        };
+
+    // Anything added while a build is running is picked up once it completes.
+    let Some(navmesh) = navmesh.filter(|_| ready && task.0.is_none()) else {
+        if task.0.is_none() {
+            *status = NavMeshStatus::Pending;
+        }
+        return;
+    };
+
+    debug!(
+        "updating navmesh with {} affectors",
+        pending.footprints.len()
+    );
+    let layers = navmesh.layers.clone();
+    let footprints = std::mem::take(&mut pending.footprints);
+    let context = settings.build_context(
+        navmesh.dimensions,
+        heightfield.as_deref(),
+        navmesh.inputs_hash,
+    );
+    pending.frames_waited = 0;
+    pending.apply_requested = false;
+    task.0 = Some(
+        AsyncComputeTaskPool::get()
+            .spawn(async move { build_navmesh(layers, footprints, context, false) }),
+    );
+    *status = NavMeshStatus::Running;
+}
+
+fn poll_navmesh_task(
+    mut task: ResMut<NavMeshTask>,
+    navmesh: Option<ResMut<PolyNavMesh>>,
     mut navmeshes: ResMut<Assets<PathMesh>>,
+    pending: Res<PendingFootprints>,
+    mut status: ResMut<NavMeshStatus>,
+    mut generated_events: EventWriter<NavMeshGenerated>,
+    mut updated_events: EventWriter<NavMeshUpdated>,
 ) {
+    let (Some(running), Some(mut navmesh)) = (task.0.as_mut(), navmesh) else {
+        return;
+    };
+    let Some(generated) = future::block_on(future::poll_once(running)) else {
+        return;
+    };
+    task.0 = None;
+
+    navmesh.layers = generated.layers;
+    if generated.rebuild {
+        navmesh.dimensions = generated.dimensions;
+        navmesh.origin = (-generated.dimensions.0 * 0.5, -generated.dimensions.1 * 0.5);
+    }
+    navmesh.inputs_hash = generated.inputs_hash;
+    navmesh.min_island_area = generated.min_island_area;
+    navmesh.graph = generated.graph;
+    navmesh.meshes = generated.pathmeshes.iter().map(PathMesh::get).collect();
+    let navmesh_handles: Vec<_> = generated
+        .pathmeshes
+        .into_iter()
+        .map(|pathmesh| navmeshes.add(pathmesh))
+        .collect();
+    let previous_handles = std::mem::replace(&mut navmesh.navmesh_handles, navmesh_handles.clone());
+    for previous_handle in previous_handles {
+        navmeshes.remove(previous_handle);
+    }
+
+    if generated.rebuild {
+        generated_events.send(NavMeshGenerated {
+            navmesh_handles,
+            stats: generated.stats,
+        });
+    } else {
+        updated_events.send(NavMeshUpdated {
+            navmesh_handles,
+            stats: generated.stats,
+            affectors: generated.affectors,
+        });
+    }
+
+    *status = if pending.footprints.is_empty() && !pending.rebuild_requested {
+        NavMeshStatus::Done
+    } else {
+        NavMeshStatus::Pending
+    };
+}
+
+/// The size of the navmesh, from the [`NavHeightField`] on the XZ plane or the
+/// [`NavMeshBounds2d`] on the XY plane.
+fn navmesh_dimensions(
+    plane: NavMeshPlane,
+    heightfield: Option<&NavHeightField>,
+    bounds_2d: Option<&NavMeshBounds2d>,
+) -> Option<(f32, f32)> {
+    match plane {
+        NavMeshPlane::Xz => heightfield.map(|heightfield| {
+            let scale = heightfield.heightfield.scale();
+            (scale.x, scale.z)
+        }),
+        NavMeshPlane::Xy => bounds_2d.map(|bounds| (bounds.size.x, bounds.size.y)),
+    }
+}
+
+fn bounding_cdt(dimensions: (f32, f32)) -> ConstrainedDelaunayTriangulation<Point2<f32>> {
     let mut cdt = ConstrainedDelaunayTriangulation::<Point2<_>>::new();
 
     // Insert the outer corners of the heightfield into the cdt.
-    let scale = heightfield.heightfield.scale();
-    let hscale = scale * 0.5;
+    let half_x = dimensions.0 * 0.5;
+    let half_z = dimensions.1 * 0.5;
 
-    println!("hscale: {:?}", hscale);
-    cdt.insert(Point2::new(-hscale.x, -hscale.z))
+    cdt.insert(Point2::new(-half_x, -half_z))
         .expect("failed to insert vertex into cdt");
-    cdt.insert(Point2::new(-hscale.x, hscale.z))
+    cdt.insert(Point2::new(-half_x, half_z))
         .expect("failed to insert vertex into cdt");
-    cdt.insert(Point2::new(hscale.x, hscale.z))
+    cdt.insert(Point2::new(half_x, half_z))
         .expect("failed to insert vertex into cdt");
-    cdt.insert(Point2::new(hscale.x, -hscale.z))
+    cdt.insert(Point2::new(half_x, -half_z))
         .expect("failed to insert vertex into cdt");
     // Insert the outer vertices of the heightfield into the cdt.
 
@@ -174,128 +1062,932 @@ fn gen_navmesh<C: PolyanyaCollider>(
     //     //     .expect("failed to insert vertex into cdt");
     // }
 
-    for (_entity, collider, transform) in collider_query.iter() {
-        let shape = collider.into_typed_shape();
-        add_collider_to_navmesh(shape, transform, &mut cdt);
+    cdt
+}
+
+/// Runs on the [`AsyncComputeTaskPool`].
+fn build_navmesh(
+    mut layers: Vec<LayerMesh>,
+    footprints: Vec<AffectorFootprint>,
+    context: BuildContext,
+    rebuild: bool,
+) -> GeneratedNavMesh {
+    let _span = info_span!("build_navmesh", rebuild).entered();
+    let now = Instant::now();
+    let affectors = footprints.len();
+    let mut affector_entities: Vec<Entity> = footprints
+        .iter()
+        .map(|footprint| footprint.entity)
+        .collect();
+    affector_entities.dedup();
+    let inputs_hash = footprints
+        .iter()
+        .map(AffectorFootprint::inputs_hash)
+        .fold(context.inputs_hash, u64::wrapping_add);
+    {
+        let _span = info_span!("insert_affectors", affectors).entered();
+        for footprint in footprints {
+            let ground = context
+                .heightfield
+                .as_ref()
+                .and_then(|heightfield| footprint_ground_height(heightfield, &footprint.footprint));
+            for layer in layers.iter_mut() {
+                let band = layer.layer.agent_band(ground, context.agent_height);
+                if !layer.layer.includes(&footprint, band) {
+                    continue;
+                }
+                let band = layer
+                    .layer
+                    .slice_band(footprint.kind, ground, context.agent_height);
+                let outlines = add_collider_to_navmesh(
+                    &footprint.footprint,
+                    context.wall_half_width,
+                    band,
+                    &mut layer.cdt,
+                );
+                for outline in outlines {
+                    layer.regions.add(footprint.kind, outline);
+                }
+            }
+        }
     }
 
-    let navmesh = into_polyanya_mesh(&cdt);
-    let pathmesh = PathMesh::from_polyanya_mesh(navmesh);
-    let navmesh_handle = navmeshes.add(pathmesh);
+    for layer in layers.iter_mut() {
+        layer.update_navigable_faces();
+    }
 
-    commands.insert_resource(PolyNavMesh {
-        cdt,
-        navmesh_handle,
-        dimensions: (scale.x, scale.z),
-    });
-}
+    let mut stats = NavMeshBuildStats {
+        affectors,
+        ..default()
+    };
+    let graph = NavMeshGraph::from_layers(
+        layers
+            .iter()
+            .map(|layer| (&layer.cdt, &layer.navigable_faces)),
+    );
+    let pathmeshes = pathmesh_trimeshes(&layers, &graph, context.min_island_area)
+        .into_iter()
+        .zip(&layers)
+        .enumerate()
+        .map(|(i, (trimesh, layer))| {
+            let navmesh: polyanya::Mesh = trimesh.into();
+            if context.validate {
+                for problem in validate_mesh(&navmesh, context.min_island_area) {
+                    warn!("Navmesh layer {} is invalid: {}", i, problem);
+                }
+            }
+            stats.vertices += navmesh.vertices.len();
+            stats.polygons += navmesh.polygons.len();
+            stats.constraints += layer.cdt.num_constraints();
+            let _span = info_span!("from_polyanya_mesh").entered();
+            PathMesh::from_polyanya_mesh(navmesh)
+        })
+        .collect();
+    stats.build_time = now.elapsed();
 
-fn update_navmesh<C: PolyanyaCollider>(
-    // mut commands: Commands,
-    collider_query: Query<(Entity, &C, &GlobalTransform), (With<NavMeshAffector>, Added<C>)>,
-    mut navmesh: ResMut<PolyNavMesh>,
-    mut navmeshes: ResMut<Assets<PathMesh>>,
-) {
-    let mut has_changed = false;
-    let cdt = &mut navmesh.cdt;
-    for (_entity, collider, transform) in collider_query.iter() {
-        has_changed = true;
-        let shape = collider.into_typed_shape();
-        add_collider_to_navmesh(shape, transform, cdt);
-    }
-    if has_changed {
-        println!("updating navmesh");
-        let mesh = into_polyanya_mesh(cdt);
-        let pathmesh = PathMesh::from_polyanya_mesh(mesh);
-        let current_handle = navmesh.navmesh_handle.clone();
-        let navmesh_handle = navmeshes.add(pathmesh);
-        navmeshes.remove(current_handle);
-        navmesh.navmesh_handle = navmesh_handle;
+    GeneratedNavMesh {
+        layers,
+        dimensions: context.dimensions,
+        inputs_hash,
+        min_island_area: context.min_island_area,
+        graph,
+        pathmeshes,
+        rebuild,
+        stats,
+        affectors: affector_entities,
     }
 }
 
+/// The navigable triangles of each layer, without islands smaller than `min_island_area`.
+fn pathmesh_trimeshes(
+    layers: &[LayerMesh],
+    graph: &NavMeshGraph,
+    min_island_area: f32,
+) -> Vec<polyanya::Trimesh> {
+    let _span = info_span!("pathmesh_trimeshes").entered();
+    // Graph polygons are numbered through the layers in order.
+    let mut first_polygon = 0;
+    layers
+        .iter()
+        .map(|layer| {
+            let mut polygon_count = 0;
+            let trimesh = navigable_trimesh_where(&layer.cdt, &layer.navigable_faces, |polygon| {
+                polygon_count += 1;
+                graph
+                    .island(first_polygon + polygon)
+                    .and_then(|island| graph.island_area(island))
+                    .map_or(true, |area| area >= min_island_area)
+            });
+            first_polygon += polygon_count;
+            trimesh
+        })
+        .collect()
+}
+
+/// The lowest point of the ground under any of the footprint's vertices.
+fn footprint_ground_height(heightfield: &HeightField, footprint: &Footprint) -> Option<f32> {
+    let points: Vec<Point2<f32>> = match footprint {
+        Footprint::Closed(points) | Footprint::Open(points) => points.clone(),
+        Footprint::Solid(solid) => solid.vertices.iter().map(|(point, _)| *point).collect(),
+        Footprint::Mesh(mesh) => mesh.vertices.iter().map(|(point, _)| *point).collect(),
+    };
+    points
+        .iter()
+        .filter_map(|point| heightfield_height_at(heightfield, *point))
+        .reduce(f32::min)
+}
+
+/// Solids are sliced between the `band` heights. Returns the closed outlines that were inserted.
 fn add_collider_to_navmesh(
-    shape: TypedShape,
-    transform: &GlobalTransform,
+    footprint: &Footprint,
+    wall_half_width: f32,
+    (bottom, top): (f32, f32),
     cdt: &mut ConstrainedDelaunayTriangulation<Point2<f32>>,
-) {
-    let points = handle_shape(shape, transform);
-    let mut handles = Vec::with_capacity(points.len());
-    for point in points {
-        handles.push(cdt.insert(point).expect("failed to insert vertex into cdt"));
-    }
-    for i in 0..handles.len() {
-        let j = (i + 1) % handles.len();
-        cdt.add_constraint(handles[i], handles[j]);
+) -> Vec<Vec<Point2<f32>>> {
+    let outlines = match footprint {
+        Footprint::Closed(points) => vec![points.clone()],
+        // Only enclosed areas are cut out of the navmesh, so walls are given some thickness
+        // rather than being added as an open chain of constraints.
+        Footprint::Open(points) => thicken_polyline(points, wall_half_width),
+        Footprint::Solid(solid) => vec![solid.slice(bottom, top)],
+        Footprint::Mesh(mesh) => mesh.slice(bottom, top),
+    };
+
+    let outlines: Vec<_> = outlines
+        .into_iter()
+        .filter(|outline| !outline.is_empty())
+        .collect();
+    for points in &outlines {
+        let mut handles = Vec::with_capacity(points.len());
+        for point in points.iter() {
+            handles.push(
+                cdt.insert(*point)
+                    .expect("failed to insert vertex into cdt"),
+            );
+        }
+        // Outlines may cross ones that are already in, such as overlapping areas or an obstacle
+        // on the edge of a floor, so crossing constraints are split where they meet.
+        for i in 0..handles.len() {
+            let j = (i + 1) % handles.len();
+            cdt.add_constraint_and_split(handles[i], handles[j], |point| point);
+        }
     }
+    outlines
 }
 
-fn handle_shape(
+/// Segments used to approximate round shapes.
+const CIRCLE_SEGMENTS: u32 = 16;
+
+pub(crate) fn handle_shape(
     shape: TypedShape,
     transform: &GlobalTransform,
     // cdt: &mut ConstrainedDelaunayTriangulation<Point2<f32>>,
-) -> Vec<Point2<f32>> {
+) -> Vec<Footprint> {
+    let to_point = |point: &parry3d::math::Point<f32>| {
+        let point = transform.transform_point(Vec3::new(point.x, point.y, point.z));
+        Point2::new(point.x, point.z)
+    };
+    let to_solid = |vertices: &[parry3d::math::Point<f32>], triangles: &[[u32; 3]]| {
+        let vertices: Vec<Vec3> = vertices
+            .iter()
+            .map(|point| transform.transform_point(Vec3::new(point.x, point.y, point.z)))
+            .collect();
+        Footprint::Solid(ConvexSolid::from_trimesh(
+            &vertices,
+            triangles,
+            NavMeshPlane::Xz,
+        ))
+    };
+
     match shape {
         TypedShape::Cuboid(cube) => {
-            // Use the half_extents for cubes
-            let a = transform.transform_point(Vec3::new(
-                -cube.half_extents.x,
-                0.0,
-                -cube.half_extents.z,
-            ));
-            let b = transform.transform_point(Vec3::new(
-                -cube.half_extents.x,
-                0.0,
-                cube.half_extents.z,
-            ));
-            let c =
-                transform.transform_point(Vec3::new(cube.half_extents.x, 0.0, cube.half_extents.z));
-            let d = transform.transform_point(Vec3::new(
-                cube.half_extents.x,
-                0.0,
-                -cube.half_extents.z,
-            ));
-
-            vec![
-                Point2::new(a.x, a.z),
-                Point2::new(b.x, b.z),
-                Point2::new(c.x, c.z),
-                Point2::new(d.x, d.z),
-            ]
+            let (vertices, triangles) = cube.to_trimesh();
+            vec![to_solid(&vertices, &triangles)]
+        }
+        TypedShape::Compound(compound) => compound
+            .shapes()
+            .iter()
+            .flat_map(|(isometry, shape)| {
+                let translation = isometry.translation.vector;
+                let rotation = isometry.rotation.coords;
+                let local = Transform::from_xyz(translation.x, translation.y, translation.z)
+                    .with_rotation(Quat::from_xyzw(
+                        rotation.x, rotation.y, rotation.z, rotation.w,
+                    ));
+                handle_shape(shape.as_typed_shape(), &transform.mul_transform(local))
+            })
+            .collect(),
+        TypedShape::Ball(ball) => {
+            let (vertices, triangles) = ball.to_trimesh(CIRCLE_SEGMENTS, CIRCLE_SEGMENTS / 2);
+            vec![to_solid(&vertices, &triangles)]
+        }
+        TypedShape::Capsule(capsule) => {
+            let (vertices, triangles) = capsule.to_trimesh(CIRCLE_SEGMENTS, CIRCLE_SEGMENTS / 2);
+            vec![to_solid(&vertices, &triangles)]
+        }
+        TypedShape::Segment(segment) => {
+            vec![Footprint::Open(vec![
+                to_point(&segment.a),
+                to_point(&segment.b),
+            ])]
+        }
+        TypedShape::Triangle(triangle) => {
+            vec![to_solid(
+                &[triangle.a, triangle.b, triangle.c],
+                &[[0, 1, 2]],
+            )]
+        }
+        TypedShape::TriMesh(trimesh) => {
+            let vertices: Vec<Vec3> = trimesh
+                .vertices()
+                .iter()
+                .map(|point| transform.transform_point(Vec3::new(point.x, point.y, point.z)))
+                .collect();
+            vec![Footprint::Mesh(SolidMesh::from_trimesh(
+                &vertices,
+                trimesh.indices(),
+                NavMeshPlane::Xz,
+            ))]
+        }
+        TypedShape::ConvexPolyhedron(polyhedron) => {
+            let (vertices, triangles) = polyhedron.to_trimesh();
+            vec![to_solid(&vertices, &triangles)]
+        }
+        TypedShape::Cylinder(cylinder) => {
+            let (vertices, triangles) = cylinder.to_trimesh(CIRCLE_SEGMENTS);
+            vec![to_solid(&vertices, &triangles)]
+        }
+        TypedShape::Cone(cone) => {
+            let (vertices, triangles) = cone.to_trimesh(CIRCLE_SEGMENTS);
+            vec![to_solid(&vertices, &triangles)]
+        }
+        // Rounded shapes are approximated by their inner shape, grown by the border radius where
+        // the shape has extents to grow.
+        TypedShape::RoundCuboid(round) => {
+            let cuboid = Cuboid::new(
+                round
+                    .inner_shape
+                    .half_extents
+                    .add_scalar(round.border_radius),
+            );
+            handle_shape(TypedShape::Cuboid(&cuboid), transform)
+        }
+        TypedShape::RoundTriangle(round) => {
+            handle_shape(TypedShape::Triangle(&round.inner_shape), transform)
+        }
+        TypedShape::RoundCylinder(round) => {
+            let cylinder = Cylinder::new(
+                round.inner_shape.half_height + round.border_radius,
+                round.inner_shape.radius + round.border_radius,
+            );
+            handle_shape(TypedShape::Cylinder(&cylinder), transform)
+        }
+        TypedShape::RoundCone(round) => {
+            let cone = Cone::new(
+                round.inner_shape.half_height + round.border_radius,
+                round.inner_shape.radius + round.border_radius,
+            );
+            handle_shape(TypedShape::Cone(&cone), transform)
+        }
+        TypedShape::RoundConvexPolyhedron(round) => {
+            handle_shape(TypedShape::ConvexPolyhedron(&round.inner_shape), transform)
+        }
+        TypedShape::Polyline(polyline) => {
+            let points: Vec<_> = polyline.vertices().iter().map(to_point).collect();
+            polyline_chains(&points, polyline.indices())
+                .into_iter()
+                .map(Footprint::Open)
+                .collect()
         }
-        TypedShape::Compound(_compound) => {
-            // We need to boolean the shapes together to get the outer points.
-            // for shape in compound.shapes() {
-            // }
-            // // We only want the outer points of the compound shape, otherwise this won't work.
-            // let mut points = Vec::new();
-            // for shape in compound.shapes() {
-            //     let mut shape_points = handle_shape(shape.1.as_typed_shape(), transform);
-
-            //     points.append(&mut shape_points);
-            // }
-
-            // compound.aabbs()
-
-            // points
-            todo!()
-        }
-        TypedShape::Ball(_) => todo!(),
-        TypedShape::Capsule(_) => todo!(),
-        TypedShape::Segment(_) => todo!(),
-        TypedShape::Triangle(_) => todo!(),
-        TypedShape::TriMesh(_) => todo!(),
-        TypedShape::ConvexPolyhedron(_) => todo!(),
-        TypedShape::Cylinder(_) => todo!(),
-        TypedShape::Cone(_) => todo!(),
-        TypedShape::RoundCuboid(_) => todo!(),
-        TypedShape::RoundTriangle(_) => todo!(),
-        TypedShape::RoundCylinder(_) => todo!(),
-        TypedShape::RoundCone(_) => todo!(),
-        TypedShape::RoundConvexPolyhedron(_) => todo!(),
-        TypedShape::Polyline(_) => panic!("Cannot use Polyline as a navmesh affector."),
         TypedShape::Custom(_) => panic!("Cannot use Custom as a navmesh affector."),
         TypedShape::HalfSpace(_) => panic!("Cannot use HalfSpace as a navmesh affector."),
         TypedShape::HeightField(_) => panic!("Cannot use HeightField as a navmesh affector."),
     }
 }
+
+#[cfg(feature = "2d")]
+pub(crate) fn handle_shape_2d(
+    shape: parry2d::shape::TypedShape,
+    transform: &GlobalTransform,
+) -> Vec<Footprint> {
+    use parry2d::shape::TypedShape;
+
+    let to_point = |x: f32, y: f32| {
+        let point = transform.transform_point(Vec3::new(x, y, 0.0));
+        Point2::new(point.x, point.y)
+    };
+
+    match shape {
+        TypedShape::Cuboid(cube) => {
+            let half_extents = cube.half_extents;
+            vec![Footprint::Closed(vec![
+                to_point(-half_extents.x, -half_extents.y),
+                to_point(half_extents.x, -half_extents.y),
+                to_point(half_extents.x, half_extents.y),
+                to_point(-half_extents.x, half_extents.y),
+            ])]
+        }
+        TypedShape::Triangle(triangle) => vec![Footprint::Closed(
+            [triangle.a, triangle.b, triangle.c]
+                .iter()
+                .map(|point| to_point(point.x, point.y))
+                .collect(),
+        )],
+        TypedShape::ConvexPolygon(polygon) => vec![Footprint::Closed(
+            polygon
+                .points()
+                .iter()
+                .map(|point| to_point(point.x, point.y))
+                .collect(),
+        )],
+        TypedShape::Segment(segment) => vec![Footprint::Open(vec![
+            to_point(segment.a.x, segment.a.y),
+            to_point(segment.b.x, segment.b.y),
+        ])],
+        TypedShape::Polyline(polyline) => {
+            let points: Vec<_> = polyline
+                .vertices()
+                .iter()
+                .map(|point| to_point(point.x, point.y))
+                .collect();
+            polyline_chains(&points, polyline.indices())
+                .into_iter()
+                .map(Footprint::Open)
+                .collect()
+        }
+        TypedShape::Ball(ball) => vec![Footprint::Closed(
+            circle_points(ball.radius, CIRCLE_SEGMENTS as usize)
+                .iter()
+                .map(|point| to_point(point.x, point.y))
+                .collect(),
+        )],
+        TypedShape::Capsule(capsule) => {
+            let segment = capsule.segment;
+            vec![Footprint::Closed(
+                capsule_points(
+                    Vec2::new(segment.a.x, segment.a.y),
+                    Vec2::new(segment.b.x, segment.b.y),
+                    capsule.radius,
+                    CIRCLE_SEGMENTS as usize / 2 + 1,
+                )
+                .iter()
+                .map(|point| to_point(point.x, point.y))
+                .collect(),
+            )]
+        }
+        TypedShape::Compound(compound) => compound
+            .shapes()
+            .iter()
+            .flat_map(|(isometry, shape)| {
+                let local = Transform::from_xyz(
+                    isometry.translation.vector.x,
+                    isometry.translation.vector.y,
+                    0.0,
+                )
+                .with_rotation(Quat::from_rotation_z(isometry.rotation.angle()));
+                handle_shape_2d(shape.as_typed_shape(), &transform.mul_transform(local))
+            })
+            .collect(),
+        TypedShape::TriMesh(_) => unsupported_shape_2d("TriMesh"),
+        TypedShape::RoundCuboid(_) => unsupported_shape_2d("RoundCuboid"),
+        TypedShape::RoundTriangle(_) => unsupported_shape_2d("RoundTriangle"),
+        TypedShape::RoundConvexPolygon(_) => unsupported_shape_2d("RoundConvexPolygon"),
+        TypedShape::Custom(_) => unsupported_shape_2d("Custom"),
+        TypedShape::HalfSpace(_) => unsupported_shape_2d("HalfSpace"),
+        TypedShape::HeightField(_) => unsupported_shape_2d("HeightField"),
+    }
+}
+
+#[cfg(feature = "2d")]
+fn unsupported_shape_2d(name: &str) -> Vec<Footprint> {
+    warn!(
+        "{} colliders can't be used as navmesh affectors and are ignored.",
+        name
+    );
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn overlapping_areas_use_the_last_one_added() {
+        let road = NavMeshArea { id: 1, cost: 0.5 };
+        let swamp = NavMeshArea { id: 2, cost: 4.0 };
+        let layer = testing::layer(
+            (20.0, 20.0),
+            &[
+                (
+                    AffectorKind::Area(road),
+                    testing::rectangle((-4.0, -1.0), (4.0, 1.0)),
+                ),
+                (
+                    AffectorKind::Area(swamp),
+                    testing::rectangle((-1.0, -4.0), (1.0, 4.0)),
+                ),
+            ],
+        );
+
+        for face in layer.cdt.inner_faces() {
+            let centre = face.center();
+            let in_road = centre.x.abs() < 4.0 && centre.y.abs() < 1.0;
+            let in_swamp = centre.x.abs() < 1.0 && centre.y.abs() < 4.0;
+            let expected = match (in_road, in_swamp) {
+                (_, true) => Some(swamp),
+                (true, false) => Some(road),
+                (false, false) => None,
+            };
+            assert_eq!(layer.regions.area_at(centre), expected);
+        }
+    }
+
+    #[test]
+    fn navigable_faces_are_classified_like_their_centres() {
+        let road = NavMeshArea { id: 1, cost: 0.5 };
+        let layer = testing::layer(
+            (20.0, 20.0),
+            &[
+                (
+                    AffectorKind::Walkable,
+                    testing::rectangle((-6.0, -6.0), (6.0, 6.0)),
+                ),
+                (
+                    AffectorKind::Obstacle,
+                    testing::rectangle((-2.0, -2.0), (2.0, 2.0)),
+                ),
+                (
+                    AffectorKind::Area(road),
+                    testing::rectangle((1.0, -8.0), (3.0, 8.0)),
+                ),
+            ],
+        );
+
+        for face in layer.cdt.inner_faces() {
+            let centre = face.center();
+            let expected = layer
+                .regions
+                .is_navigable(centre)
+                .then(|| layer.regions.area_at(centre).unwrap_or_default());
+            assert_eq!(layer.navigable_faces.get(&face.index()).copied(), expected);
+        }
+    }
+
+    #[test]
+    fn obstacles_can_cross_the_edge_of_a_floor() {
+        let layer = testing::layer(
+            (20.0, 20.0),
+            &[
+                (
+                    AffectorKind::Walkable,
+                    testing::rectangle((-5.0, -5.0), (5.0, 5.0)),
+                ),
+                (
+                    AffectorKind::Obstacle,
+                    testing::rectangle((3.0, -1.0), (7.0, 1.0)),
+                ),
+            ],
+        );
+
+        for face in layer.cdt.inner_faces() {
+            let centre = face.center();
+            let on_floor = centre.x.abs() < 5.0 && centre.y.abs() < 5.0;
+            let in_obstacle = centre.x > 3.0 && centre.x < 7.0 && centre.y.abs() < 1.0;
+            assert_eq!(layer.regions.is_navigable(centre), on_floor && !in_obstacle);
+        }
+    }
+
+    fn affector(
+        kind: AffectorKind,
+        min: (f32, f32),
+        max: (f32, f32),
+        vertical_extent: Option<(f32, f32)>,
+    ) -> AffectorFootprint {
+        AffectorFootprint {
+            entity: Entity::PLACEHOLDER,
+            footprint: testing::rectangle(min, max),
+            kind,
+            vertical_extent,
+        }
+    }
+
+    #[test]
+    fn agents_stand_on_the_ground_or_the_floor() {
+        let storey = NavMeshLayer {
+            floor: 5.0,
+            ceiling: 10.0,
+        };
+        assert_eq!(storey.agent_band(None, 2.0), (5.0, 7.0));
+        assert_eq!(storey.agent_band(Some(0.0), 2.0), (5.0, 7.0));
+        assert_eq!(storey.agent_band(Some(6.0), 2.0), (6.0, 8.0));
+        // Agents don't reach through the ceiling.
+        assert_eq!(storey.agent_band(Some(9.0), 2.0), (9.0, 10.0));
+
+        let everything = NavMeshLayer::default();
+        assert_eq!(everything.agent_band(Some(1.0), 2.0), (1.0, 3.0));
+        assert_eq!(
+            everything.agent_band(None, 2.0),
+            (f32::NEG_INFINITY, f32::INFINITY)
+        );
+    }
+
+    #[test]
+    fn layers_include_what_agents_would_bump_into() {
+        let storey = NavMeshLayer {
+            floor: 0.0,
+            ceiling: 5.0,
+        };
+        let band = (0.0, 2.0);
+        let includes =
+            |kind, extent| storey.includes(&affector(kind, (0.0, 0.0), (1.0, 1.0), extent), band);
+
+        assert!(includes(AffectorKind::Obstacle, None));
+        assert!(includes(AffectorKind::Obstacle, Some((1.0, 3.0))));
+        assert!(includes(AffectorKind::Obstacle, Some((-1.0, 0.0))));
+        // Overhead and underground.
+        assert!(!includes(AffectorKind::Obstacle, Some((2.5, 4.0))));
+        assert!(!includes(AffectorKind::Obstacle, Some((-3.0, -1.0))));
+
+        // Ground belongs to the layer its top is in.
+        let road = AffectorKind::Area(NavMeshArea { id: 1, cost: 0.5 });
+        for kind in [AffectorKind::Walkable, road] {
+            assert!(includes(kind, None));
+            assert!(includes(kind, Some((-1.0, 4.0))));
+            assert!(!includes(kind, Some((0.0, 5.0))));
+            assert!(!includes(kind, Some((-2.0, -1.0))));
+        }
+    }
+
+    /// A ground floor and a 10 by 10 upper floor at height 5 over it, joined by stairs. A pillar
+    /// goes through both floors and a canopy hangs over the ground floor below the upper one.
+    fn storeys() -> PolyNavMesh {
+        let dimensions = (20.0, 20.0);
+        let layers = [(0.0, 5.0), (5.0, f32::INFINITY)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (floor, ceiling))| {
+                let regions = NavMeshRegions {
+                    walkable_only: i > 0,
+                    ..default()
+                };
+                LayerMesh::new(
+                    bounding_cdt(dimensions),
+                    regions,
+                    NavMeshLayer { floor, ceiling },
+                )
+            })
+            .collect();
+        let canopy = obstacle::NavObstacle::Closed {
+            vertices: vec![
+                Vec2::new(-1.0, -1.0),
+                Vec2::new(1.0, -1.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(-1.0, 1.0),
+            ],
+            height: Some(1.0),
+        };
+        let canopy_extent = obstacle::NavObstacleFootprint::vertical_extent(
+            &canopy,
+            &GlobalTransform::from_xyz(7.0, 3.0, 7.0),
+            NavMeshPlane::Xz,
+        );
+        assert_eq!(canopy_extent, Some((3.0, 4.0)));
+        let footprints = vec![
+            affector(
+                AffectorKind::Walkable,
+                (-5.0, -5.0),
+                (5.0, 5.0),
+                Some((5.0, 5.0)),
+            ),
+            affector(
+                AffectorKind::Obstacle,
+                (-1.0, -1.0),
+                (1.0, 1.0),
+                Some((0.0, 10.0)),
+            ),
+            affector(
+                AffectorKind::Obstacle,
+                (6.0, 6.0),
+                (8.0, 8.0),
+                canopy_extent,
+            ),
+        ];
+        let context = BuildContext {
+            dimensions,
+            wall_half_width: 0.1,
+            agent_height: 2.0,
+            heightfield: None,
+            inputs_hash: 0,
+            min_island_area: 0.0,
+            validate: false,
+        };
+        let generated = build_navmesh(layers, footprints, context, true);
+        let mut navmesh = testing::navmesh(dimensions, generated.layers);
+
+        let stairs = OffMeshLink {
+            start: Vec3::new(-7.0, 0.0, 3.0),
+            end: Vec3::new(-4.0, 5.0, 3.0),
+            bidirectional: true,
+            cost: 1.0,
+        };
+        let (bottom, top) = ((0, Vec2::new(-7.0, 3.0)), (1, Vec2::new(-4.0, 3.0)));
+        assert!(navmesh
+            .graph
+            .add_link(Entity::PLACEHOLDER, bottom, top, &stairs));
+        navmesh
+    }
+
+    #[test]
+    fn affectors_are_split_between_layers() {
+        let navmesh = storeys();
+        let graph = navmesh.graph();
+        let on = |layer, x, y| graph.polygon_at_layer(layer, Vec2::new(x, y)).is_some();
+
+        // The pillar blocks both floors.
+        assert!(!on(0, 0.0, 0.0));
+        assert!(!on(1, 0.0, 0.0));
+        // The upper floor only covers its own walkable ground.
+        assert!(on(0, 3.0, 3.0));
+        assert!(on(1, 3.0, 3.0));
+        assert!(on(0, -8.0, -8.0));
+        assert!(!on(1, -8.0, -8.0));
+        // The canopy is over the agents' heads on the ground floor and under the upper floor.
+        assert!(on(0, 7.0, 7.0));
+        assert!(!on(1, 7.0, 7.0));
+    }
+
+    #[test]
+    fn layers_are_found_by_height() {
+        let navmesh = storeys();
+        assert_eq!(navmesh.layer_at(-1.0), None);
+        assert_eq!(navmesh.layer_at(0.0), Some(0));
+        assert_eq!(navmesh.layer_at(4.9), Some(0));
+        assert_eq!(navmesh.layer_at(5.0), Some(1));
+        assert_eq!(navmesh.layer_at(100.0), Some(1));
+    }
+
+    #[test]
+    fn paths_go_up_the_stairs() {
+        let navmesh = storeys();
+        let (ground, upstairs) = (Vec3::new(-8.0, 0.0, -8.0), Vec3::new(3.0, 5.0, 3.0));
+        assert!(navmesh.is_reachable_3d(ground, upstairs));
+        assert!(navmesh.is_reachable_3d(upstairs, ground));
+        // Off the upper floor, and below every layer.
+        assert!(!navmesh.is_reachable_3d(ground, Vec3::new(8.0, 5.0, 8.0)));
+        assert!(!navmesh.is_reachable_3d(Vec3::new(-8.0, -1.0, -8.0), upstairs));
+
+        let path = navmesh
+            .find_path_3d(ground, upstairs, AreaMask::ALL)
+            .unwrap();
+        assert_eq!(path.links.len(), 1);
+        let waypoint = path.links[0].waypoint;
+        assert_eq!(path.path[waypoint], Vec2::new(-7.0, 3.0));
+        assert_eq!(path.path[waypoint + 1], Vec2::new(-4.0, 3.0));
+        // The link goes from the ground floor to the upper one.
+        assert_eq!(path.layers.len(), path.path.len());
+        assert_eq!(path.layers[waypoint], 0);
+        assert_eq!(path.layers[waypoint + 1], 1);
+        assert_eq!(path.path.first(), Some(&Vec2::new(-8.0, -8.0)));
+        assert_eq!(path.path.last(), Some(&Vec2::new(3.0, 3.0)));
+
+        // Walking around the pillar is all on the ground floor.
+        let path = navmesh
+            .find_path_3d(ground, Vec3::new(8.0, 0.0, 8.0), AreaMask::ALL)
+            .unwrap();
+        assert!(path.links.is_empty());
+    }
+
+    #[test]
+    fn small_islands_are_left_out_of_the_pathmesh() {
+        // A 3 by 3 garden walled off from the rest of the layer.
+        let walls = [
+            ((2.0, 2.0), (6.0, 2.5)),
+            ((2.0, 5.5), (6.0, 6.0)),
+            ((2.0, 2.5), (2.5, 5.5)),
+            ((5.5, 2.5), (6.0, 5.5)),
+        ]
+        .map(|(min, max)| (AffectorKind::Obstacle, testing::rectangle(min, max)));
+        let layers = vec![testing::layer((20.0, 20.0), &walls)];
+        let graph = testing::graph(&layers);
+
+        let area = |trimesh: &polyanya::Trimesh| -> f32 {
+            trimesh
+                .triangles
+                .iter()
+                .map(|&[a, b, c]| {
+                    let [a, b, c] = [a, b, c].map(|i| trimesh.vertices[i]);
+                    (b - a).perp_dot(c - a).abs() * 0.5
+                })
+                .sum()
+        };
+        let everything = area(&pathmesh_trimeshes(&layers, &graph, 0.0)[0]);
+        assert!((everything - (400.0 - 16.0 + 9.0)).abs() < 1e-2);
+        let without_garden = area(&pathmesh_trimeshes(&layers, &graph, 10.0)[0]);
+        assert!((without_garden - (400.0 - 16.0)).abs() < 1e-2);
+        let nothing = &pathmesh_trimeshes(&layers, &graph, 1000.0)[0];
+        assert!(nothing.triangles.is_empty());
+    }
+
+    /// The corners of the box around each footprint of `shape`, sliced over its full height.
+    fn shape_bounds(shape: SharedShape, transform: Transform) -> Vec<(Vec2, Vec2)> {
+        handle_shape(shape.as_typed_shape(), &GlobalTransform::from(transform))
+            .iter()
+            .map(|footprint| {
+                let Footprint::Solid(solid) = footprint else {
+                    panic!("expected a solid footprint, got {:?}", footprint);
+                };
+                let outline = solid.slice(f32::NEG_INFINITY, f32::INFINITY);
+                outline.iter().fold(
+                    (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+                    |(min, max), point| {
+                        let point = Vec2::new(point.x, point.y);
+                        (min.min(point), max.max(point))
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn assert_bounds(bounds: (Vec2, Vec2), min: (f32, f32), max: (f32, f32)) {
+        let expected = (Vec2::new(min.0, min.1), Vec2::new(max.0, max.1));
+        assert!(
+            bounds.0.abs_diff_eq(expected.0, 1e-4) && bounds.1.abs_diff_eq(expected.1, 1e-4),
+            "{:?} != {:?}",
+            bounds,
+            expected
+        );
+    }
+
+    #[test]
+    fn compound_parts_keep_their_own_placement() {
+        let compound = SharedShape::compound(vec![
+            (
+                parry3d::math::Isometry::translation(-3.0, 0.0, 0.0),
+                SharedShape::cuboid(1.0, 1.0, 1.0),
+            ),
+            (
+                parry3d::math::Isometry::new(
+                    parry3d::math::Vector::new(3.0, 0.0, 0.0),
+                    parry3d::math::Vector::y() * std::f32::consts::FRAC_PI_2,
+                ),
+                SharedShape::cuboid(2.0, 1.0, 1.0),
+            ),
+        ]);
+        let bounds = shape_bounds(compound, Transform::from_xyz(0.0, 0.0, 5.0));
+
+        assert_eq!(bounds.len(), 2);
+        assert_bounds(bounds[0], (-4.0, 4.0), (-2.0, 6.0));
+        assert_bounds(bounds[1], (2.0, 3.0), (4.0, 7.0));
+    }
+
+    #[test]
+    fn triangles_cover_their_projected_corners() {
+        let triangle = SharedShape::triangle(
+            parry3d::math::Point::new(0.0, 0.0, 0.0),
+            parry3d::math::Point::new(2.0, 1.0, 0.0),
+            parry3d::math::Point::new(0.0, 2.0, 2.0),
+        );
+        let bounds = shape_bounds(triangle, Transform::from_xyz(1.0, 0.0, 1.0));
+
+        assert_eq!(bounds.len(), 1);
+        assert_bounds(bounds[0], (1.0, 1.0), (3.0, 3.0));
+    }
+
+    #[test]
+    fn round_shapes_grow_by_their_border() {
+        let cases = [
+            (SharedShape::round_cuboid(1.0, 1.0, 2.0, 0.5), 1.5, 2.5),
+            (SharedShape::round_cylinder(1.0, 1.0, 0.5), 1.5, 1.5),
+            (SharedShape::round_cone(1.0, 1.0, 0.5), 1.5, 1.5),
+        ];
+        for (shape, x, z) in cases {
+            let bounds = shape_bounds(shape, Transform::IDENTITY);
+            assert_eq!(bounds.len(), 1);
+            assert_bounds(bounds[0], (-x, -z), (x, z));
+        }
+
+        let corners = [
+            parry3d::math::Point::new(-1.0, -1.0, -1.0),
+            parry3d::math::Point::new(1.0, -1.0, -1.0),
+            parry3d::math::Point::new(0.0, -1.0, 1.0),
+            parry3d::math::Point::new(0.0, 1.0, 0.0),
+        ];
+        let hull = SharedShape::round_convex_hull(&corners, 0.5).unwrap();
+        let bounds = shape_bounds(hull, Transform::IDENTITY);
+        assert_eq!(bounds.len(), 1);
+        assert_bounds(bounds[0], (-1.0, -1.0), (1.0, 1.0));
+
+        let triangle = SharedShape::round_triangle(
+            parry3d::math::Point::new(0.0, 0.0, 0.0),
+            parry3d::math::Point::new(1.0, 0.0, 0.0),
+            parry3d::math::Point::new(0.0, 0.0, 1.0),
+            0.5,
+        );
+        let bounds = shape_bounds(triangle, Transform::IDENTITY);
+        assert_eq!(bounds.len(), 1);
+        assert_bounds(bounds[0], (0.0, 0.0), (1.0, 1.0));
+    }
+
+    #[cfg(feature = "2d")]
+    fn footprints_2d(shape: parry2d::shape::SharedShape, transform: Transform) -> Vec<Footprint> {
+        handle_shape_2d(shape.as_typed_shape(), &GlobalTransform::from(transform))
+    }
+
+    #[cfg(feature = "2d")]
+    fn outline_bounds(points: &[Point2<f32>]) -> (Vec2, Vec2) {
+        points.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), point| {
+                let point = Vec2::new(point.x, point.y);
+                (min.min(point), max.max(point))
+            },
+        )
+    }
+
+    #[cfg(feature = "2d")]
+    #[test]
+    fn capsules_are_projected_with_both_ends() {
+        let capsule = parry2d::shape::SharedShape::capsule(
+            parry2d::math::Point::new(-2.0, 0.0),
+            parry2d::math::Point::new(2.0, 0.0),
+            1.0,
+        );
+        let transform = Transform::from_xyz(10.0, 5.0, 0.0)
+            .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2));
+        let footprints = footprints_2d(capsule, transform);
+
+        assert_eq!(footprints.len(), 1);
+        let Footprint::Closed(outline) = &footprints[0] else {
+            panic!("expected a closed footprint, got {:?}", footprints[0]);
+        };
+        assert_bounds(outline_bounds(outline), (9.0, 2.0), (11.0, 8.0));
+        // Every point is on the rounded surface, a radius away from the segment.
+        for point in outline {
+            let distance = Vec2::new(point.x - 10.0, (point.y - 5.0).abs() - 2.0)
+                .max(Vec2::new(f32::NEG_INFINITY, 0.0))
+                .length();
+            assert!((distance - 1.0).abs() < 1e-4, "{:?}", point);
+        }
+    }
+
+    #[cfg(feature = "2d")]
+    #[test]
+    fn compound_parts_keep_their_own_placement_in_2d() {
+        let compound = parry2d::shape::SharedShape::compound(vec![
+            (
+                parry2d::math::Isometry::translation(-3.0, 0.0),
+                parry2d::shape::SharedShape::cuboid(1.0, 1.0),
+            ),
+            (
+                parry2d::math::Isometry::new(
+                    parry2d::math::Vector::new(3.0, 0.0),
+                    std::f32::consts::FRAC_PI_2,
+                ),
+                parry2d::shape::SharedShape::cuboid(2.0, 1.0),
+            ),
+        ]);
+        let footprints = footprints_2d(compound, Transform::from_xyz(0.0, 5.0, 0.0));
+
+        assert_eq!(footprints.len(), 2);
+        let bounds: Vec<_> = footprints
+            .iter()
+            .map(|footprint| {
+                let Footprint::Closed(outline) = footprint else {
+                    panic!("expected a closed footprint, got {:?}", footprint);
+                };
+                outline_bounds(outline)
+            })
+            .collect();
+        assert_bounds(bounds[0], (-4.0, 4.0), (-2.0, 6.0));
+        assert_bounds(bounds[1], (2.0, 3.0), (4.0, 7.0));
+    }
+
+    #[cfg(feature = "2d")]
+    #[test]
+    fn polylines_are_split_into_open_chains() {
+        let vertices = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (5.0, 5.0), (6.0, 5.0)]
+            .map(|(x, y)| parry2d::math::Point::new(x, y))
+            .to_vec();
+        let polyline =
+            parry2d::shape::SharedShape::polyline(vertices, Some(vec![[0, 1], [1, 2], [3, 4]]));
+        let footprints = footprints_2d(polyline, Transform::from_xyz(1.0, 2.0, 0.0));
+
+        let chains: Vec<Vec<(f32, f32)>> = footprints
+            .iter()
+            .map(|footprint| {
+                let Footprint::Open(points) = footprint else {
+                    panic!("expected an open footprint, got {:?}", footprint);
+                };
+                points.iter().map(|point| (point.x, point.y)).collect()
+            })
+            .collect();
+        assert_eq!(
+            chains,
+            vec![
+                vec![(1.0, 2.0), (2.0, 2.0), (2.0, 3.0)],
+                vec![(6.0, 7.0), (7.0, 7.0)],
+            ]
+        );
+    }
+}
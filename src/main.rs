@@ -2,8 +2,12 @@ use std::sync::Arc;
 
 use bevy::{prelude::*, window::PrimaryWindow};
 use bevy_poly_navmesh::{
-    colliders::parry3d_collider::Parry3dCollider, utils::NavMeshAffector, DrawCdt, NavHeightField,
-    NavMeshSettings, PolyanyaNavMeshPlugin,
+    colliders::parry3d_collider::Parry3dCollider,
+    debug::{NavMeshDebugPlugin, NavMeshDebugSettings},
+    diagnostics::NavMeshDiagnosticsPlugin,
+    obstacle::{NavObstacle, NavObstaclePlugin},
+    utils::NavMeshAffector,
+    NavHeightField, NavMeshSettings, PolyanyaNavMeshPlugin,
 };
 use camera_controller::{CameraController, CameraControllerPlugin};
 use parry3d::{
@@ -17,10 +21,12 @@ fn main() {
     App::new()
         .add_plugins((
             DefaultPlugins,
-            PolyanyaNavMeshPlugin::<Parry3dCollider>::new(NavMeshSettings {}),
+            PolyanyaNavMeshPlugin::<Parry3dCollider>::new(NavMeshSettings::default()),
+            NavObstaclePlugin,
+            NavMeshDiagnosticsPlugin,
+            NavMeshDebugPlugin,
             CameraControllerPlugin,
         ))
-        .insert_resource(DrawCdt(true))
         .add_systems(Startup, setup)
         .add_systems(Update, (draw_heightfield, add_obstacle))
         .run();
@@ -62,8 +68,12 @@ fn setup(
     });
 }
 
-fn draw_heightfield(heightfield: Res<NavHeightField>, mut gizmos: Gizmos, draw_cdt: Res<DrawCdt>) {
-    if !draw_cdt.0 {
+fn draw_heightfield(
+    heightfield: Res<NavHeightField>,
+    mut gizmos: Gizmos,
+    debug_settings: Res<NavMeshDebugSettings>,
+) {
+    if !debug_settings.enabled {
         for tri in heightfield.heightfield.triangles() {
             let vertices = tri.vertices();
             let a = vertices[0].coords;
@@ -90,7 +100,7 @@ pub fn add_obstacle(
     primary_window: Query<&Window, With<PrimaryWindow>>,
     camera_q: Query<(&Camera, &GlobalTransform)>,
 ) {
-    if input.just_pressed(MouseButton::Left) {
+    if input.any_just_pressed([MouseButton::Left, MouseButton::Right]) {
         let (camera, camera_transform) = camera_q.single();
         let window = primary_window.single();
         if let Some(point) = window
@@ -105,15 +115,25 @@ pub fn add_obstacle(
             })
             .flatten()
         {
-            // obstacles.obstacles.push(Obstacle::Closed {
-            //     // CCW winding order for
-            //     vertices: vec![
-            //         Vec2::new(point.x - HALF_WIDTH, point.z - HALF_HEIGHT),
-            //         Vec2::new(point.x + HALF_WIDTH, point.z - HALF_HEIGHT),
-            //         Vec2::new(point.x + HALF_WIDTH, point.z + HALF_HEIGHT),
-            //         Vec2::new(point.x - HALF_WIDTH, point.z + HALF_HEIGHT),
-            //     ],
-            // });
+            let transform = Transform::from_translation(Vec3::new(point.x, 0.0, point.z));
+
+            // Right click places a designer style obstacle with no physics shape.
+            if input.just_pressed(MouseButton::Right) {
+                commands.spawn((
+                    TransformBundle::from_transform(transform),
+                    NavObstacle::Closed {
+                        vertices: vec![
+                            Vec2::new(-HALF_WIDTH, -HALF_HEIGHT),
+                            Vec2::new(HALF_WIDTH, -HALF_HEIGHT),
+                            Vec2::new(HALF_WIDTH, HALF_HEIGHT),
+                            Vec2::new(-HALF_WIDTH, HALF_HEIGHT),
+                        ],
+                        height: None,
+                    },
+                    NavMeshAffector,
+                ));
+                return;
+            }
 
             let mesh = Mesh::from(shape::Cube {
                 size: HALF_HEIGHT * 2.0,
@@ -123,7 +143,7 @@ pub fn add_obstacle(
                 PbrBundle {
                     mesh: meshes.add(mesh),
                     material,
-                    transform: Transform::from_translation(Vec3::new(point.x, 0.0, point.z)),
+                    transform,
                     ..Default::default()
                 },
                 Parry3dCollider {
@@ -0,0 +1,98 @@
+use bevy::prelude::*;
+use spade::Point2;
+
+use crate::{
+    add_navmesh_collider,
+    colliders::{ColliderFootprint, Footprint, NavMeshPlane},
+    utils::circle_points,
+};
+
+const CIRCLE_SEGMENTS: usize = 16;
+
+/// A hand authored navmesh blocker that doesn't need a physics shape. Coordinates are in the
+/// entity's local space on the navmesh plane, so `Vec2::new(x, y)` is `(x, 0, y)` on the XZ plane.
+///
+/// Like colliders, obstacles only affect the navmesh while the entity also has a [`NavMeshAffector`](crate::utils::NavMeshAffector).
+/// `height` is how far the obstacle reaches up from the entity's origin, and picks the layers it
+/// blocks like a collider's bounds do. Without one it blocks every layer whatever the height of the
+/// entity or the ground.
+#[derive(Component, Clone, Debug)]
+pub enum NavObstacle {
+    /// Blocks the area enclosed by the vertices.
+    Closed {
+        vertices: Vec<Vec2>,
+        height: Option<f32>,
+    },
+    /// A wall or fence along the vertices.
+    Open {
+        vertices: Vec<Vec2>,
+        height: Option<f32>,
+    },
+    /// Blocks a circle around the entity's origin.
+    Circle { radius: f32, height: Option<f32> },
+}
+
+impl NavObstacle {
+    pub fn height(&self) -> Option<f32> {
+        match self {
+            NavObstacle::Closed { height, .. }
+            | NavObstacle::Open { height, .. }
+            | NavObstacle::Circle { height, .. } => *height,
+        }
+    }
+}
+
+pub struct NavObstacleFootprint;
+
+impl ColliderFootprint for NavObstacleFootprint {
+    type Collider = NavObstacle;
+
+    fn footprint(
+        obstacle: &NavObstacle,
+        transform: &GlobalTransform,
+        plane: NavMeshPlane,
+    ) -> Vec<Footprint> {
+        let to_navmesh = |vertex: &Vec2| {
+            let point = plane.to_plane(transform.transform_point(plane.to_world(*vertex, 0.0)));
+            Point2::new(point.x, point.y)
+        };
+
+        let footprint = match obstacle {
+            NavObstacle::Closed { vertices, .. } => {
+                Footprint::Closed(vertices.iter().map(to_navmesh).collect())
+            }
+            NavObstacle::Open { vertices, .. } => {
+                Footprint::Open(vertices.iter().map(to_navmesh).collect())
+            }
+            NavObstacle::Circle { radius, .. } => Footprint::Closed(
+                circle_points(*radius, CIRCLE_SEGMENTS)
+                    .iter()
+                    .map(to_navmesh)
+                    .collect(),
+            ),
+        };
+        vec![footprint]
+    }
+
+    fn vertical_extent(
+        obstacle: &NavObstacle,
+        transform: &GlobalTransform,
+        plane: NavMeshPlane,
+    ) -> Option<(f32, f32)> {
+        let height = obstacle.height()?;
+        let [base, top] = [0.0, height].map(|height| {
+            plane.height(transform.transform_point(plane.to_world(Vec2::ZERO, height)))
+        });
+        Some((base.min(top), base.max(top)))
+    }
+}
+
+/// Adds [`NavObstacle`]s to the navmesh built by the navmesh plugin.
+#[derive(Default)]
+pub struct NavObstaclePlugin;
+
+impl Plugin for NavObstaclePlugin {
+    fn build(&self, app: &mut App) {
+        add_navmesh_collider::<NavObstacleFootprint>(app);
+    }
+}
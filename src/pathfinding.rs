@@ -0,0 +1,943 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use bevy::{prelude::*, utils::HashMap};
+use spade::{ConstrainedDelaunayTriangulation, Point2, Triangulation};
+
+use crate::utils::NavMeshArea;
+
+/// The navigable triangles of every navmesh layer with the area each one belongs to. Unlike the
+/// [`bevy_pathmesh::PathMesh`], searches over this take area costs into account.
+#[derive(Clone, Debug, Default)]
+pub struct NavMeshGraph {
+    polygons: Vec<GraphPolygon>,
+    links: Vec<GraphLink>,
+    /// The island each polygon is in.
+    islands: Vec<usize>,
+    island_areas: Vec<f32>,
+    /// Union-find parents of the islands, joining the ones connected by off-mesh links.
+    joined_islands: Vec<usize>,
+    grid: PolygonGrid,
+}
+
+#[derive(Clone, Debug)]
+struct GraphPolygon {
+    /// Counter-clockwise.
+    vertices: [Vec2; 3],
+    /// `neighbours[i]` is across the edge from `vertices[i]` to `vertices[(i + 1) % 3]`.
+    neighbours: [Option<usize>; 3],
+    area: NavMeshArea,
+    layer: usize,
+}
+
+impl GraphPolygon {
+    fn centre(&self) -> Vec2 {
+        (self.vertices[0] + self.vertices[1] + self.vertices[2]) / 3.0
+    }
+
+    fn surface_area(&self) -> f32 {
+        let [a, b, c] = self.vertices;
+        (b - a).perp_dot(c - a).abs() * 0.5
+    }
+
+    fn bounds(&self) -> (Vec2, Vec2) {
+        let [a, b, c] = self.vertices;
+        (a.min(b).min(c), a.max(b).max(c))
+    }
+
+    fn contains(&self, point: Vec2) -> bool {
+        (0..3).all(|i| {
+            let a = self.vertices[i];
+            let b = self.vertices[(i + 1) % 3];
+            (b - a).perp_dot(point - a) >= -f32::EPSILON
+        })
+    }
+
+    /// The shared edge to `neighbour` as seen when walking into it, as `(left, right)`.
+    fn portal(&self, neighbour: usize) -> Option<(Vec2, Vec2)> {
+        let i = self.neighbours.iter().position(|n| *n == Some(neighbour))?;
+        Some((self.vertices[(i + 1) % 3], self.vertices[i]))
+    }
+}
+
+/// Buckets the polygons by the grid cells their bounds overlap, so finding the polygon under a point
+/// only tests the few polygons in its cell.
+#[derive(Clone, Debug, Default)]
+struct PolygonGrid {
+    min: Vec2,
+    cell_size: Vec2,
+    columns: usize,
+    rows: usize,
+    /// The polygons overlapping each cell, row by row. Each list is in ascending order.
+    cells: Vec<Vec<usize>>,
+}
+
+impl PolygonGrid {
+    fn new(polygons: &[GraphPolygon]) -> Self {
+        let Some((min, max)) = polygons
+            .iter()
+            .map(GraphPolygon::bounds)
+            .reduce(|(min, max), (low, high)| (min.min(low), max.max(high)))
+        else {
+            return Self::default();
+        };
+        // Aim for about one polygon per cell.
+        let size = (max - min).max(Vec2::splat(f32::EPSILON));
+        let side = (size.x * size.y / polygons.len() as f32).sqrt();
+        let count = |length: f32| ((length / side).ceil() as usize).clamp(1, polygons.len());
+        let (columns, rows) = (count(size.x), count(size.y));
+
+        let mut grid = Self {
+            min,
+            cell_size: size / Vec2::new(columns as f32, rows as f32),
+            columns,
+            rows,
+            cells: vec![Vec::new(); columns * rows],
+        };
+        for (index, polygon) in polygons.iter().enumerate() {
+            let (low, high) = polygon.bounds();
+            let ((first_column, first_row), (last_column, last_row)) =
+                (grid.cell(low), grid.cell(high));
+            for row in first_row..=last_row {
+                for column in first_column..=last_column {
+                    grid.cells[row * columns + column].push(index);
+                }
+            }
+        }
+        grid
+    }
+
+    /// The column and row of the cell containing `point`, clamped to the grid.
+    fn cell(&self, point: Vec2) -> (usize, usize) {
+        let cell = ((point - self.min) / self.cell_size)
+            .floor()
+            .max(Vec2::ZERO);
+        (
+            (cell.x as usize).min(self.columns - 1),
+            (cell.y as usize).min(self.rows - 1),
+        )
+    }
+
+    /// The polygons that may contain `point`, lowest index first.
+    fn candidates(&self, point: Vec2) -> &[usize] {
+        if self.cells.is_empty() {
+            return &[];
+        }
+        let (column, row) = self.cell(point);
+        &self.cells[row * self.columns + column]
+    }
+}
+
+/// A jump, ladder or teleporter between two points on the navmesh that path queries may use.
+/// `start` and `end` are relative to the entity's transform.
+#[derive(Component, Clone, Debug)]
+pub struct OffMeshLink {
+    pub start: Vec3,
+    pub end: Vec3,
+    /// Whether the link can also be taken from `end` to `start`.
+    pub bidirectional: bool,
+    /// Added to the cost of any path that takes the link.
+    pub cost: f32,
+}
+
+/// One direction of an [`OffMeshLink`].
+#[derive(Clone, Debug)]
+struct GraphLink {
+    entity: Entity,
+    start: Vec2,
+    end: Vec2,
+    from: usize,
+    to: usize,
+    cost: f32,
+}
+
+/// Which [`NavMeshArea`] ids a path query may go through. Put one on an agent to give it its own
+/// filter, e.g. `AreaMask::NONE.allow(WATER)` for boats or `AreaMask::ALL.forbid(FOREST)` for tanks.
+/// Holds a bit for each of the 256 possible ids, the lowest 64 ids in the first word.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AreaMask(pub [u64; 4]);
+
+impl AreaMask {
+    pub const ALL: Self = Self([u64::MAX; 4]);
+    pub const NONE: Self = Self([0; 4]);
+
+    pub fn allow(mut self, id: u8) -> Self {
+        let (word, bit) = Self::bit(id);
+        self.0[word] |= bit;
+        self
+    }
+
+    pub fn forbid(mut self, id: u8) -> Self {
+        let (word, bit) = Self::bit(id);
+        self.0[word] &= !bit;
+        self
+    }
+
+    pub fn contains(self, id: u8) -> bool {
+        let (word, bit) = Self::bit(id);
+        self.0[word] & bit != 0
+    }
+
+    fn bit(id: u8) -> (usize, u64) {
+        (id as usize / 64, 1 << (id % 64))
+    }
+}
+
+impl Default for AreaMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// A path found by [`NavMeshGraph::find_path`]. Put it on the agent following it to have
+/// [`NavMeshDebugPlugin`](crate::debug::NavMeshDebugPlugin) draw it.
+#[derive(Component, Clone, Debug, Default)]
+pub struct NavPath {
+    /// Distance travelled, scaled by the cost of the areas it goes through.
+    pub cost: f32,
+    pub length: f32,
+    pub path: Vec<Vec2>,
+    /// The layer each waypoint of `path` is on.
+    pub layers: Vec<usize>,
+    /// The off-mesh links taken along the path, in order.
+    pub links: Vec<PathLink>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PathLink {
+    /// Index into [`NavPath::path`] of the start of the link. The next waypoint is its end.
+    pub waypoint: usize,
+    pub entity: Entity,
+}
+
+struct CorridorStep {
+    polygon: usize,
+    /// The link taken to get into `polygon`, if it wasn't entered through a shared edge.
+    link: Option<usize>,
+}
+
+impl NavMeshGraph {
+    /// Builds the graph from the triangulation of each layer, lowest first, and its navigable
+    /// faces as given by [`compute_navigable_faces`](crate::utils::compute_navigable_faces). Layers
+    /// are only connected by off-mesh links.
+    pub fn from_layers<'a>(
+        layers: impl IntoIterator<
+            Item = (
+                &'a ConstrainedDelaunayTriangulation<Point2<f32>>,
+                &'a HashMap<usize, NavMeshArea>,
+            ),
+        >,
+    ) -> Self {
+        let _span = info_span!("build_navmesh_graph").entered();
+        let mut polygons = Vec::new();
+        for (layer, (cdt, navigable_faces)) in layers.into_iter().enumerate() {
+            let offset = polygons.len();
+            let indices: HashMap<usize, usize> = cdt
+                .inner_faces()
+                .filter(|face| navigable_faces.contains_key(&face.index()))
+                .enumerate()
+                .map(|(polygon, face)| (face.index(), offset + polygon))
+                .collect();
+
+            polygons.extend(
+                cdt.inner_faces()
+                    .filter_map(|face| Some((face, *navigable_faces.get(&face.index())?)))
+                    .map(|(face, area)| {
+                        let edges = face.adjacent_edges();
+                        let vertices = edges.map(|edge| {
+                            let position = edge.from().position();
+                            Vec2::new(position.x, position.y)
+                        });
+                        let neighbours = edges.map(|edge| {
+                            edge.rev()
+                                .face()
+                                .as_inner()
+                                .and_then(|neighbour| indices.get(&neighbour.index()).copied())
+                        });
+                        GraphPolygon {
+                            vertices,
+                            neighbours,
+                            area,
+                            layer,
+                        }
+                    }),
+            );
+        }
+
+        let (islands, island_areas) = find_islands(&polygons);
+        Self {
+            grid: PolygonGrid::new(&polygons),
+            polygons,
+            links: Vec::new(),
+            islands,
+            joined_islands: (0..island_areas.len()).collect(),
+            island_areas,
+        }
+    }
+
+    pub fn polygon_count(&self) -> usize {
+        self.polygons.len()
+    }
+
+    /// The index of the polygon containing `point` on the lowest layer it's on, if any.
+    pub fn polygon_at(&self, point: Vec2) -> Option<usize> {
+        self.locate(point, |_| true)
+    }
+
+    /// The index of the polygon containing `point` on `layer`, if it is on the navmesh.
+    pub fn polygon_at_layer(&self, layer: usize, point: Vec2) -> Option<usize> {
+        self.locate(point, |polygon| polygon.layer == layer)
+    }
+
+    /// The lowest indexed polygon containing `point` that passes `filter`.
+    fn locate(&self, point: Vec2, filter: impl Fn(&GraphPolygon) -> bool) -> Option<usize> {
+        self.grid.candidates(point).iter().copied().find(|&index| {
+            let polygon = &self.polygons[index];
+            filter(polygon) && polygon.contains(point)
+        })
+    }
+
+    pub fn layer(&self, polygon: usize) -> Option<usize> {
+        self.polygons.get(polygon).map(|polygon| polygon.layer)
+    }
+
+    pub fn area(&self, polygon: usize) -> Option<NavMeshArea> {
+        self.polygons.get(polygon).map(|polygon| polygon.area)
+    }
+
+    /// The island `polygon` is in. Islands are groups of polygons connected through shared edges,
+    /// found whenever the navmesh is built.
+    pub fn island(&self, polygon: usize) -> Option<usize> {
+        self.islands.get(polygon).copied()
+    }
+
+    pub fn island_count(&self) -> usize {
+        self.island_areas.len()
+    }
+
+    /// The surface area of all of the island's polygons.
+    pub fn island_area(&self, island: usize) -> Option<f32> {
+        self.island_areas.get(island).copied()
+    }
+
+    /// Quickly rules out impossible path queries on the lowest layer. See
+    /// [`Self::is_reachable_between_layers`].
+    pub fn is_reachable(&self, from: Vec2, to: Vec2) -> bool {
+        self.is_reachable_between_layers((0, from), (0, to))
+    }
+
+    /// Whether `from` and `to` are on the navmesh and connected, through shared edges or off-mesh
+    /// links. Links count in both directions and area masks are ignored, so a path query may still
+    /// fail when this is true, but never succeeds when it is false.
+    pub fn is_reachable_between_layers(
+        &self,
+        (from_layer, from): (usize, Vec2),
+        (to_layer, to): (usize, Vec2),
+    ) -> bool {
+        let (Some(start), Some(goal)) = (
+            self.polygon_at_layer(from_layer, from),
+            self.polygon_at_layer(to_layer, to),
+        ) else {
+            return false;
+        };
+        self.joined_island(self.islands[start]) == self.joined_island(self.islands[goal])
+    }
+
+    fn joined_island(&self, mut island: usize) -> usize {
+        while self.joined_islands[island] != island {
+            island = self.joined_islands[island];
+        }
+        island
+    }
+
+    /// Connects the polygons under `start` and `end`, given as a layer and a point in plane
+    /// coordinates. Returns false if either end isn't on the navmesh, in which case the link is
+    /// ignored.
+    pub fn add_link(
+        &mut self,
+        entity: Entity,
+        (start_layer, start): (usize, Vec2),
+        (end_layer, end): (usize, Vec2),
+        link: &OffMeshLink,
+    ) -> bool {
+        let (Some(from), Some(to)) = (
+            self.polygon_at_layer(start_layer, start),
+            self.polygon_at_layer(end_layer, end),
+        ) else {
+            return false;
+        };
+        self.links.push(GraphLink {
+            entity,
+            start,
+            end,
+            from,
+            to,
+            cost: link.cost,
+        });
+        let (from_island, to_island) = (
+            self.joined_island(self.islands[from]),
+            self.joined_island(self.islands[to]),
+        );
+        self.joined_islands[from_island] = to_island;
+        if link.bidirectional {
+            self.links.push(GraphLink {
+                entity,
+                start: end,
+                end: start,
+                from: to,
+                to: from,
+                cost: link.cost,
+            });
+        }
+        true
+    }
+
+    pub fn clear_links(&mut self) {
+        self.links.clear();
+        self.joined_islands = (0..self.island_areas.len()).collect();
+    }
+
+    /// Finds the cheapest path from `from` to `to` on the lowest layer, preferring cheap areas over
+    /// short distances.
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Option<NavPath> {
+        self.find_path_filtered(from, to, AreaMask::ALL)
+    }
+
+    /// Like [`Self::find_path`], treating polygons whose area isn't in `mask` as blocked.
+    pub fn find_path_filtered(&self, from: Vec2, to: Vec2, mask: AreaMask) -> Option<NavPath> {
+        self.find_path_between_layers((0, from), (0, to), mask)
+    }
+
+    /// Finds a path between points on different layers, given as a layer and a point in plane
+    /// coordinates. The path can only change layers through off-mesh links.
+    pub fn find_path_between_layers(
+        &self,
+        (from_layer, from): (usize, Vec2),
+        (to_layer, to): (usize, Vec2),
+        mask: AreaMask,
+    ) -> Option<NavPath> {
+        let _span = info_span!("find_path").entered();
+        let locate = |layer: usize, point: Vec2| {
+            self.locate(point, |polygon| {
+                polygon.layer == layer && mask.contains(polygon.area.id)
+            })
+        };
+        let start = locate(from_layer, from)?;
+        let goal = locate(to_layer, to)?;
+        let (corridor, cost) = self.find_corridor(start, goal, from, to, mask)?;
+
+        // Each stretch between off-mesh links is straightened on its own.
+        let mut path = Vec::new();
+        let mut layers = Vec::new();
+        let mut links = Vec::new();
+        let mut stretch_start = from;
+        let mut stretch = vec![corridor[0].polygon];
+        let mut pull = |stretch: &[usize], from: Vec2, to: Vec2, path: &mut Vec<Vec2>| {
+            let points = self.string_pull(stretch, from, to);
+            let layer = self.polygons[stretch[0]].layer;
+            layers.extend(std::iter::repeat(layer).take(points.len()));
+            path.extend(points);
+        };
+        for step in &corridor[1..] {
+            match step.link {
+                Some(link) => {
+                    let link = &self.links[link];
+                    pull(&stretch, stretch_start, link.start, &mut path);
+                    links.push(PathLink {
+                        waypoint: path.len() - 1,
+                        entity: link.entity,
+                    });
+                    stretch_start = link.end;
+                    stretch = vec![step.polygon];
+                }
+                None => stretch.push(step.polygon),
+            }
+        }
+        pull(&stretch, stretch_start, to, &mut path);
+
+        let length = path
+            .windows(2)
+            .map(|segment| segment[0].distance(segment[1]))
+            .sum();
+        Some(NavPath {
+            cost,
+            length,
+            path,
+            layers,
+            links,
+        })
+    }
+
+    /// A* over the polygons and off-mesh links, using polygon centres as waypoints.
+    fn find_corridor(
+        &self,
+        start: usize,
+        goal: usize,
+        from: Vec2,
+        to: Vec2,
+        mask: AreaMask,
+    ) -> Option<(Vec<CorridorStep>, f32)> {
+        // Scaling the heuristic by the cheapest area keeps it admissible, unless a link costs less
+        // than walking the distance it covers.
+        let min_cost = self
+            .polygons
+            .iter()
+            .map(|polygon| polygon.area.cost)
+            .fold(f32::INFINITY, f32::min)
+            .max(0.0);
+        let shortcut = self
+            .links
+            .iter()
+            .any(|link| link.cost < link.start.distance(link.end) * min_cost);
+        let min_cost = if shortcut { 0.0 } else { min_cost };
+        let position = |polygon: usize| {
+            if polygon == goal {
+                to
+            } else if polygon == start {
+                from
+            } else {
+                self.polygons[polygon].centre()
+            }
+        };
+
+        let mut best: HashMap<usize, f32> = HashMap::new();
+        let mut came_from: HashMap<usize, (usize, Option<usize>)> = HashMap::new();
+        let mut open = BinaryHeap::new();
+        best.insert(start, 0.0);
+        open.push(SearchNode {
+            estimate: from.distance(to) * min_cost,
+            polygon: start,
+        });
+
+        while let Some(SearchNode { polygon, .. }) = open.pop() {
+            let cost = best[&polygon];
+            if polygon == goal {
+                let mut corridor = Vec::new();
+                let mut current = goal;
+                while let Some(&(previous, link)) = came_from.get(&current) {
+                    corridor.push(CorridorStep {
+                        polygon: current,
+                        link,
+                    });
+                    current = previous;
+                }
+                corridor.push(CorridorStep {
+                    polygon: start,
+                    link: None,
+                });
+                corridor.reverse();
+                return Some((corridor, cost));
+            }
+
+            let current = &self.polygons[polygon];
+            let walks = current.neighbours.iter().flatten().map(|&neighbour| {
+                let step = position(polygon).distance(position(neighbour))
+                    * (current.area.cost + self.polygons[neighbour].area.cost)
+                    * 0.5;
+                (neighbour, None, step)
+            });
+            let links = self
+                .links
+                .iter()
+                .enumerate()
+                .filter(|(_, link)| link.from == polygon)
+                .map(|(index, link)| {
+                    let step = position(polygon).distance(link.start) * current.area.cost
+                        + link.cost
+                        + link.end.distance(position(link.to)) * self.polygons[link.to].area.cost;
+                    (link.to, Some(index), step)
+                });
+
+            for (neighbour, link, step) in walks.chain(links) {
+                if !mask.contains(self.polygons[neighbour].area.id) {
+                    continue;
+                }
+                let cost = cost + step;
+                if best.get(&neighbour).map_or(true, |best| cost < *best) {
+                    best.insert(neighbour, cost);
+                    came_from.insert(neighbour, (polygon, link));
+                    open.push(SearchNode {
+                        estimate: cost + position(neighbour).distance(to) * min_cost,
+                        polygon: neighbour,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Straightens the path through `corridor` with the simple stupid funnel algorithm.
+    fn string_pull(&self, corridor: &[usize], from: Vec2, to: Vec2) -> Vec<Vec2> {
+        let mut portals: Vec<(Vec2, Vec2)> = corridor
+            .windows(2)
+            .filter_map(|pair| self.polygons[pair[0]].portal(pair[1]))
+            .collect();
+        portals.push((to, to));
+
+        let mut path = vec![from];
+        let (mut apex, mut left, mut right) = (from, from, from);
+        let (mut apex_index, mut left_index, mut right_index) = (0, 0, 0);
+        let mut i = 0;
+        while i < portals.len() {
+            let (portal_left, portal_right) = portals[i];
+
+            if (right - apex).perp_dot(portal_right - apex) >= 0.0 {
+                if apex == right || (left - apex).perp_dot(portal_right - apex) < 0.0 {
+                    right = portal_right;
+                    right_index = i + 1;
+                } else {
+                    // The right side crossed over the left, so the left is a corner.
+                    path.push(left);
+                    apex = left;
+                    apex_index = left_index;
+                    (left, right) = (apex, apex);
+                    (left_index, right_index) = (apex_index, apex_index);
+                    i = apex_index;
+                    continue;
+                }
+            }
+
+            if (left - apex).perp_dot(portal_left - apex) <= 0.0 {
+                if apex == left || (right - apex).perp_dot(portal_left - apex) > 0.0 {
+                    left = portal_left;
+                    left_index = i + 1;
+                } else {
+                    path.push(right);
+                    apex = right;
+                    apex_index = right_index;
+                    (left, right) = (apex, apex);
+                    (left_index, right_index) = (apex_index, apex_index);
+                    i = apex_index;
+                    continue;
+                }
+            }
+            i += 1;
+        }
+
+        if path.last() != Some(&to) {
+            path.push(to);
+        }
+        path
+    }
+}
+
+/// Labels each polygon with the island it's in, and sums the area of each island.
+fn find_islands(polygons: &[GraphPolygon]) -> (Vec<usize>, Vec<f32>) {
+    let mut islands = vec![usize::MAX; polygons.len()];
+    let mut areas = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..polygons.len() {
+        if islands[start] != usize::MAX {
+            continue;
+        }
+        let island = areas.len();
+        let mut area = 0.0;
+        islands[start] = island;
+        stack.push(start);
+        while let Some(polygon) = stack.pop() {
+            area += polygons[polygon].surface_area();
+            for neighbour in polygons[polygon].neighbours.into_iter().flatten() {
+                if islands[neighbour] == usize::MAX {
+                    islands[neighbour] = island;
+                    stack.push(neighbour);
+                }
+            }
+        }
+        areas.push(area);
+    }
+    (islands, areas)
+}
+
+struct SearchNode {
+    estimate: f32,
+    polygon: usize,
+}
+
+impl PartialEq for SearchNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SearchNode {}
+
+impl PartialOrd for SearchNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SearchNode {
+    // Reversed so the `BinaryHeap` pops the lowest estimate first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, utils::AffectorKind};
+
+    /// A 20 by 20 layer centred on zero with the given rectangles cut into it.
+    fn graph(rectangles: &[(AffectorKind, (f32, f32), (f32, f32))]) -> NavMeshGraph {
+        let affectors: Vec<_> = rectangles
+            .iter()
+            .map(|&(kind, min, max)| (kind, testing::rectangle(min, max)))
+            .collect();
+        testing::graph(&[testing::layer((20.0, 20.0), &affectors)])
+    }
+
+    fn goes_over(path: &NavPath, y: f32) -> bool {
+        path.path.iter().any(|point| point.y >= y - 1e-3)
+    }
+
+    #[test]
+    fn expensive_areas_are_walked_around() {
+        // A swamp from the bottom edge up to y = 6, between the start and the goal.
+        let swamp = NavMeshArea { id: 1, cost: 10.0 };
+        let graph = graph(&[(AffectorKind::Area(swamp), (-2.0, -10.0), (2.0, 6.0))]);
+        let path = graph
+            .find_path(Vec2::new(-6.0, 0.0), Vec2::new(6.0, 0.0))
+            .unwrap();
+        assert!(goes_over(&path, 6.0), "{:?}", path.path);
+        assert!(path.cost < 12.0 + 4.0 * 9.0);
+    }
+
+    #[test]
+    fn areas_outside_the_mask_are_avoided() {
+        let water = NavMeshArea { id: 3, cost: 1.0 };
+        let graph = graph(&[(AffectorKind::Area(water), (-2.0, -10.0), (2.0, 6.0))]);
+        let (from, to) = (Vec2::new(-6.0, 0.0), Vec2::new(6.0, 0.0));
+
+        let path = graph.find_path(from, to).unwrap();
+        assert!(!goes_over(&path, 6.0), "{:?}", path.path);
+
+        let path = graph
+            .find_path_filtered(from, to, AreaMask::ALL.forbid(3))
+            .unwrap();
+        assert!(goes_over(&path, 6.0), "{:?}", path.path);
+    }
+
+    #[test]
+    fn every_area_id_can_be_masked() {
+        for id in [0, 63, 64, 200, 255] {
+            assert!(AreaMask::ALL.contains(id));
+            assert!(!AreaMask::NONE.contains(id));
+            assert!(!AreaMask::ALL.forbid(id).contains(id));
+            assert!(AreaMask::NONE.allow(id).contains(id));
+            assert_eq!(AreaMask::NONE.allow(id).forbid(id), AreaMask::NONE);
+        }
+        assert!(!AreaMask::NONE.allow(200).contains(136));
+
+        let lava = NavMeshArea { id: 200, cost: 1.0 };
+        let graph = graph(&[(AffectorKind::Area(lava), (-2.0, -10.0), (2.0, 6.0))]);
+        let path = graph
+            .find_path_filtered(
+                Vec2::new(-6.0, 0.0),
+                Vec2::new(6.0, 0.0),
+                AreaMask::ALL.forbid(200),
+            )
+            .unwrap();
+        assert!(goes_over(&path, 6.0), "{:?}", path.path);
+    }
+
+    #[test]
+    fn paths_are_pulled_tight_around_corners() {
+        // A wall from the bottom edge up to y = 2.
+        let graph = graph(&[(AffectorKind::Obstacle, (-1.0, -10.0), (1.0, 2.0))]);
+        let (from, to) = (Vec2::new(-5.0, -5.0), Vec2::new(5.0, -5.0));
+        let path = graph.find_path(from, to).unwrap();
+
+        let expected = [from, Vec2::new(-1.0, 2.0), Vec2::new(1.0, 2.0), to];
+        assert_eq!(path.path.len(), expected.len(), "{:?}", path.path);
+        for (point, expected) in path.path.iter().zip(expected) {
+            assert!(point.distance(expected) < 1e-4, "{:?}", path.path);
+        }
+        let length = 2.0 * Vec2::new(4.0, 7.0).length() + 2.0;
+        assert!((path.length - length).abs() < 1e-3);
+    }
+
+    /// A wall across the whole layer, so the two sides are only connected by links.
+    fn split_graph() -> NavMeshGraph {
+        graph(&[(AffectorKind::Obstacle, (-1.0, -10.0), (1.0, 10.0))])
+    }
+
+    fn link(bidirectional: bool) -> OffMeshLink {
+        OffMeshLink {
+            start: Vec3::new(-2.0, 0.0, 0.0),
+            end: Vec3::new(2.0, 0.0, 0.0),
+            bidirectional,
+            cost: 3.0,
+        }
+    }
+
+    #[test]
+    fn links_join_separate_parts() {
+        let mut graph = split_graph();
+        let (left, right) = (Vec2::new(-5.0, 0.0), Vec2::new(5.0, 0.0));
+        assert!(graph.find_path(left, right).is_none());
+        assert!(!graph.is_reachable(left, right));
+
+        let entity = Entity::from_raw(7);
+        let start = (0, Vec2::new(-2.0, 0.0));
+        let end = (0, Vec2::new(2.0, 0.0));
+        assert!(graph.add_link(entity, start, end, &link(true)));
+        assert!(graph.is_reachable(left, right));
+
+        let path = graph.find_path(left, right).unwrap();
+        assert_eq!(path.path, vec![left, start.1, end.1, right]);
+        assert!((path.length - 10.0).abs() < 1e-4);
+        // The walk on either side is at least 3 long.
+        assert!(path.cost >= 3.0 + 6.0 - 1e-4);
+
+        // Links off the navmesh aren't added.
+        let outside = (0, Vec2::new(0.0, 0.0));
+        assert!(!graph.add_link(entity, start, outside, &link(true)));
+
+        graph.clear_links();
+        assert!(graph.find_path(left, right).is_none());
+        assert!(!graph.is_reachable(left, right));
+    }
+
+    #[test]
+    fn one_way_links_are_only_taken_forwards() {
+        let (left, right) = (Vec2::new(-5.0, 0.0), Vec2::new(5.0, 0.0));
+        let start = (0, Vec2::new(-2.0, 0.0));
+        let end = (0, Vec2::new(2.0, 0.0));
+
+        let mut one_way = split_graph();
+        assert!(one_way.add_link(Entity::from_raw(1), start, end, &link(false)));
+        assert!(one_way.find_path(left, right).is_some());
+        assert!(one_way.find_path(right, left).is_none());
+
+        let mut both_ways = split_graph();
+        assert!(both_ways.add_link(Entity::from_raw(1), start, end, &link(true)));
+        let path = both_ways.find_path(right, left).unwrap();
+        assert_eq!(path.path, vec![right, end.1, start.1, left]);
+    }
+
+    #[test]
+    fn links_taken_are_marked_on_their_waypoints() {
+        let mut graph = split_graph();
+        let first = Entity::from_raw(1);
+        let second = Entity::from_raw(2);
+        let start = (0, Vec2::new(-2.0, -5.0));
+        let end = (0, Vec2::new(2.0, -5.0));
+        assert!(graph.add_link(first, start, end, &link(false)));
+        assert!(graph.add_link(
+            second,
+            (0, Vec2::new(2.0, 5.0)),
+            (0, Vec2::new(-2.0, 5.0)),
+            &link(false)
+        ));
+
+        // Over the first link and back over the second.
+        let (from, to) = (Vec2::new(-5.0, -5.0), Vec2::new(-5.0, 5.0));
+        let path = graph.find_path(from, to).unwrap();
+        assert_eq!(path.links.len(), 2, "{:?}", path);
+        assert_eq!(path.links[0].entity, first);
+        assert_eq!(path.path[path.links[0].waypoint], start.1);
+        assert_eq!(path.path[path.links[0].waypoint + 1], end.1);
+        assert_eq!(path.links[1].entity, second);
+        assert_eq!(path.path[path.links[1].waypoint], Vec2::new(2.0, 5.0));
+        assert_eq!(path.path[path.links[1].waypoint + 1], Vec2::new(-2.0, 5.0));
+    }
+
+    /// A 4 by 4 walled garden on a 20 by 20 layer, with walls half a unit thick.
+    fn garden() -> [(AffectorKind, (f32, f32), (f32, f32)); 4] {
+        [
+            (AffectorKind::Obstacle, (2.0, 2.0), (6.0, 2.5)),
+            (AffectorKind::Obstacle, (2.0, 5.5), (6.0, 6.0)),
+            (AffectorKind::Obstacle, (2.0, 2.5), (2.5, 5.5)),
+            (AffectorKind::Obstacle, (5.5, 2.5), (6.0, 5.5)),
+        ]
+    }
+
+    #[test]
+    fn islands_are_split_by_obstacles() {
+        let graph = graph(&garden());
+        assert_eq!(graph.island_count(), 2);
+
+        let inside = graph.polygon_at(Vec2::new(4.0, 4.0)).unwrap();
+        let outside = graph.polygon_at(Vec2::new(-5.0, -5.0)).unwrap();
+        let (inside, outside) = (
+            graph.island(inside).unwrap(),
+            graph.island(outside).unwrap(),
+        );
+        assert_ne!(inside, outside);
+        assert!((graph.island_area(inside).unwrap() - 9.0).abs() < 1e-3);
+        assert!((graph.island_area(outside).unwrap() - (400.0 - 16.0)).abs() < 1e-2);
+        // Every polygon is in one of the two islands.
+        assert!((0..graph.polygon_count()).all(|polygon| {
+            let island = graph.island(polygon).unwrap();
+            island == inside || island == outside
+        }));
+    }
+
+    #[test]
+    fn reachability_follows_the_islands() {
+        let graph = graph(&garden());
+        let (inside, outside) = (Vec2::new(4.0, 4.0), Vec2::new(-5.0, -5.0));
+        assert!(graph.is_reachable(outside, Vec2::new(8.0, 8.0)));
+        assert!(graph.is_reachable(inside, Vec2::new(3.0, 5.0)));
+        assert!(!graph.is_reachable(outside, inside));
+        // In a wall and off the navmesh.
+        assert!(!graph.is_reachable(outside, Vec2::new(2.2, 4.0)));
+        assert!(!graph.is_reachable(outside, Vec2::new(15.0, 0.0)));
+        // There is no second layer.
+        assert!(!graph.is_reachable_between_layers((1, outside), (1, outside)));
+    }
+
+    #[test]
+    fn layers_are_only_reachable_through_links() {
+        let floor = testing::layer((20.0, 20.0), &[]);
+        let mut graph = testing::graph(&[floor.clone(), floor]);
+        let (downstairs, upstairs) = ((0, Vec2::new(-5.0, 0.0)), (1, Vec2::new(5.0, 0.0)));
+        assert!(graph.is_reachable_between_layers(downstairs, (0, upstairs.1)));
+        assert!(!graph.is_reachable_between_layers(downstairs, upstairs));
+        assert!(graph
+            .find_path_between_layers(downstairs, upstairs, AreaMask::ALL)
+            .is_none());
+
+        let stairs = OffMeshLink {
+            start: Vec3::ZERO,
+            end: Vec3::Y,
+            bidirectional: false,
+            cost: 1.0,
+        };
+        let (bottom, top) = ((0, Vec2::ZERO), (1, Vec2::ZERO));
+        assert!(graph.add_link(Entity::from_raw(1), bottom, top, &stairs));
+        assert!(graph.is_reachable_between_layers(downstairs, upstairs));
+        // Reachability ignores which way links go.
+        assert!(graph.is_reachable_between_layers(upstairs, downstairs));
+
+        let path = graph
+            .find_path_between_layers(downstairs, upstairs, AreaMask::ALL)
+            .unwrap();
+        assert_eq!(path.path, vec![downstairs.1, bottom.1, top.1, upstairs.1]);
+        assert_eq!(path.layers, vec![0, 0, 1, 1]);
+        assert!(graph
+            .find_path_between_layers(upstairs, downstairs, AreaMask::ALL)
+            .is_none());
+    }
+
+    #[test]
+    fn polygons_are_found_like_a_linear_scan() {
+        let floor = testing::layer((20.0, 20.0), &[]);
+        let garden = garden().map(|(kind, min, max)| (kind, testing::rectangle(min, max)));
+        let graph = testing::graph(&[testing::layer((20.0, 20.0), &garden), floor]);
+        for x in -22..=22 {
+            for y in -22..=22 {
+                let point = Vec2::new(x as f32, y as f32) * 0.5 + 0.01;
+                let scan = |layer: Option<usize>| {
+                    graph.polygons.iter().position(|polygon| {
+                        layer.map_or(true, |layer| polygon.layer == layer)
+                            && polygon.contains(point)
+                    })
+                };
+                assert_eq!(graph.polygon_at(point), scan(None), "{point}");
+                assert_eq!(graph.polygon_at_layer(1, point), scan(Some(1)), "{point}");
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Navmeshes for the tests to build on.
+
+use std::sync::Arc;
+
+use spade::{ConstrainedDelaunayTriangulation, Point2};
+
+use crate::{
+    add_collider_to_navmesh, bounding_cdt,
+    colliders::{Footprint, NavMeshPlane},
+    pathfinding::NavMeshGraph,
+    pathmesh_trimeshes,
+    utils::{AffectorKind, NavMeshRegions},
+    LayerMesh, NavMeshLayer, PolyNavMesh,
+};
+
+pub(crate) fn rectangle(min: (f32, f32), max: (f32, f32)) -> Footprint {
+    Footprint::Closed(vec![
+        Point2::new(min.0, min.1),
+        Point2::new(max.0, min.1),
+        Point2::new(max.0, max.1),
+        Point2::new(min.0, max.1),
+    ])
+}
+
+/// Cuts `footprint` into `cdt` over its full height and adds it to `regions` as `kind`. Returns
+/// the outlines it was cut as.
+pub(crate) fn cut(
+    cdt: &mut ConstrainedDelaunayTriangulation<Point2<f32>>,
+    regions: &mut NavMeshRegions,
+    kind: AffectorKind,
+    footprint: &Footprint,
+    wall_half_width: f32,
+) -> Vec<Vec<Point2<f32>>> {
+    let band = (f32::NEG_INFINITY, f32::INFINITY);
+    let outlines = add_collider_to_navmesh(footprint, wall_half_width, band, cdt);
+    for outline in &outlines {
+        regions.add(kind, outline.clone());
+    }
+    outlines
+}
+
+/// A layer of `dimensions` centred on zero, with `affectors` cut into it in order.
+pub(crate) fn layer(dimensions: (f32, f32), affectors: &[(AffectorKind, Footprint)]) -> LayerMesh {
+    let mut layer = LayerMesh::new(
+        bounding_cdt(dimensions),
+        NavMeshRegions::default(),
+        NavMeshLayer::default(),
+    );
+    for (kind, footprint) in affectors {
+        cut(&mut layer.cdt, &mut layer.regions, *kind, footprint, 0.1);
+    }
+    layer.update_navigable_faces();
+    layer
+}
+
+pub(crate) fn graph(layers: &[LayerMesh]) -> NavMeshGraph {
+    NavMeshGraph::from_layers(
+        layers
+            .iter()
+            .map(|layer| (&layer.cdt, &layer.navigable_faces)),
+    )
+}
+
+/// A navmesh of `dimensions` centred on zero on the XZ plane, with its polyanya meshes but without
+/// any [`bevy_pathmesh::PathMesh`] assets.
+pub(crate) fn navmesh(dimensions: (f32, f32), layers: Vec<LayerMesh>) -> PolyNavMesh {
+    let graph = graph(&layers);
+    let meshes = pathmesh_trimeshes(&layers, &graph, 0.0)
+        .into_iter()
+        .map(|trimesh| Arc::new(trimesh.into()))
+        .collect();
+    PolyNavMesh {
+        layers,
+        graph,
+        inputs_hash: 0,
+        min_island_area: 0.0,
+        navmesh_handles: Vec::new(),
+        meshes,
+        dimensions,
+        origin: (-dimensions.0 * 0.5, -dimensions.1 * 0.5),
+        plane: NavMeshPlane::Xz,
+    }
+}
@@ -1,33 +1,146 @@
+use std::hash::Hasher;
+
 use bevy::{
-    prelude::{Component, GlobalTransform, Vec2},
-    utils::{HashMap, HashSet, Instant},
+    prelude::{info_span, Component, Vec2},
+    utils::HashMap,
+};
+use parry3d::{
+    math::{Point, Vector},
+    query::{Ray, RayCast},
+    shape::HeightField,
 };
 use spade::{ConstrainedDelaunayTriangulation, Point2, Triangulation};
 
-use crate::PolyanyaCollider;
-
 #[derive(Component)]
 pub struct NavMeshAffector;
 
-pub fn into_polyanya_mesh(
+/// Turns an affector's footprint into walkable ground instead of an obstacle. Once any walkable
+/// affector exists, everything outside of them is blocked. Layers above the first are always
+/// blocked outside of them. Walkable affectors are plain ground, any [`NavMeshArea`] on them is
+/// ignored.
+#[derive(Component)]
+pub struct NavMeshWalkable;
+
+/// Turns an affector's footprint into an area with a traversal cost instead of a hole, such as a
+/// road or a swamp. Where areas overlap, the most recently added one wins.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct NavMeshArea {
+    /// Path queries can exclude areas by id with an [`crate::pathfinding::AreaMask`].
+    pub id: u8,
+    /// Multiplier applied to distances travelled inside the area.
+    pub cost: f32,
+}
+
+impl Default for NavMeshArea {
+    /// Plain ground, used outside of every area.
+    fn default() -> Self {
+        Self { id: 0, cost: 1.0 }
+    }
+}
+
+/// How an affector's footprint changes the navmesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AffectorKind {
+    Obstacle,
+    Walkable,
+    Area(NavMeshArea),
+}
+
+impl AffectorKind {
+    /// Walkable affectors can't also be areas, so `area` is ignored when `walkable` is set.
+    pub fn new(walkable: bool, area: Option<&NavMeshArea>) -> Self {
+        match (walkable, area) {
+            (true, _) => Self::Walkable,
+            (false, Some(area)) => Self::Area(*area),
+            (false, None) => Self::Obstacle,
+        }
+    }
+}
+
+/// The outlines that have been cut into the triangulation.
+#[derive(Clone, Debug, Default)]
+pub struct NavMeshRegions {
+    pub obstacles: Vec<Vec<Point2<f32>>>,
+    pub walkable: Vec<Vec<Point2<f32>>>,
+    pub areas: Vec<(NavMeshArea, Vec<Point2<f32>>)>,
+    /// Blocks everything outside of `walkable`, even while it's empty.
+    pub walkable_only: bool,
+}
+
+impl NavMeshRegions {
+    pub fn add(&mut self, kind: AffectorKind, outline: Vec<Point2<f32>>) {
+        match kind {
+            AffectorKind::Obstacle => self.obstacles.push(outline),
+            AffectorKind::Walkable => self.walkable.push(outline),
+            AffectorKind::Area(area) => self.areas.push((area, outline)),
+        }
+    }
+
+    pub fn area_at(&self, point: Point2<f32>) -> Option<NavMeshArea> {
+        self.areas
+            .iter()
+            .rev()
+            .find(|(_, outline)| point_in_polygon(point, outline))
+            .map(|(area, _)| *area)
+    }
+
+    pub fn is_navigable(&self, point: Point2<f32>) -> bool {
+        let in_walkable = (self.walkable.is_empty() && !self.walkable_only)
+            || self
+                .walkable
+                .iter()
+                .any(|outline| point_in_polygon(point, outline));
+        in_walkable
+            && !self
+                .obstacles
+                .iter()
+                .any(|outline| point_in_polygon(point, outline))
+    }
+}
+
+/// Even-odd test of whether `point` is inside the closed `outline`.
+pub fn point_in_polygon(point: Point2<f32>, outline: &[Point2<f32>]) -> bool {
+    let mut inside = false;
+    let mut j = outline.len().wrapping_sub(1);
+    for (i, a) in outline.iter().enumerate() {
+        let b = outline[j];
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// The navigable faces of the triangulation, with only the vertices they use.
+pub fn navigable_trimesh(
+    cdt: &ConstrainedDelaunayTriangulation<Point2<f32>>,
+    navigable_faces: &HashMap<usize, NavMeshArea>,
+) -> polyanya::Trimesh {
+    navigable_trimesh_where(cdt, navigable_faces, |_| true)
+}
+
+/// Like [`navigable_trimesh`], leaving out faces `keep` returns false for. `keep` is called with
+/// each navigable face's position among them, which is also its polygon index in the layer.
+pub fn navigable_trimesh_where(
     cdt: &ConstrainedDelaunayTriangulation<Point2<f32>>,
-    // navigable_faces: &HashSet<usize>,
-    // obstacle_edges: &HashSet<usize>,
-) -> polyanya::Mesh {
-    todo!()
-    // TODO: Converting a polyanya TriMesh to a Polyanya Mesh is slow, so we should just directly create a polyanya mesh.
-    let now = Instant::now();
+    navigable_faces: &HashMap<usize, NavMeshArea>,
+    mut keep: impl FnMut(usize) -> bool,
+) -> polyanya::Trimesh {
     let mut vertices: Vec<Vec2> = Vec::new();
     let mut triangles: Vec<[usize; 3]> = Vec::new();
     let mut vert_indices: HashMap<usize, usize> = HashMap::new();
-    let navigable_faces = compute_navigable_faces(&cdt);
-    for face in cdt.inner_faces() {
-        if !navigable_faces.contains(&face.index()) {
-            continue;
-        }
+    let faces = cdt
+        .inner_faces()
+        .filter(|face| navigable_faces.contains_key(&face.index()))
+        .enumerate()
+        .filter(|(polygon, _)| keep(*polygon));
+    for (_, face) in faces {
         let mut indices = [0; 3];
         face.vertices().iter().enumerate().for_each(|(i, vertex)| {
-            vertex.index();
             let temp_pos = vertex.position();
             let pos = Vec2::new(temp_pos.x, temp_pos.y);
             let idx = *vert_indices.entry(vertex.index()).or_insert_with(|| {
@@ -35,100 +148,320 @@ pub fn into_polyanya_mesh(
                 vertices.push(pos);
                 idx
             });
-            // if let Some(index) = vertices.iter().position(|v| *v == pos) {
-            //     idx = index;
-            // } else {
-            //     vertices.push(pos);
-            // }
             indices[i] = idx;
         });
         triangles.push(indices);
     }
-    let elapsed = now.elapsed();
-    println!("into_polyanya_mesh 1 took: {:?}", elapsed);
-    let mesh = polyanya::Trimesh {
+    polyanya::Trimesh {
         vertices,
         triangles,
+    }
+}
+
+/// Returns the convex hull of `points` in counter-clockwise order, using Andrew's monotone chain.
+pub fn convex_hull_2d(mut points: Vec<Point2<f32>>) -> Vec<Point2<f32>> {
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    fn cross(o: Point2<f32>, a: Point2<f32>, b: Point2<f32>) -> f32 {
+        (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+    }
+
+    fn half_hull<'a>(points: impl Iterator<Item = &'a Point2<f32>>) -> Vec<Point2<f32>> {
+        let mut hull: Vec<Point2<f32>> = Vec::new();
+        for &point in points {
+            while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        // The last point is the first point of the other half.
+        hull.pop();
+        hull
+    }
+
+    let mut hull = half_hull(points.iter());
+    hull.extend(half_hull(points.iter().rev()));
+    hull
+}
+
+/// Turns an open chain of points into closed outlines covering `half_width` either side of it: a
+/// rectangle per segment and a bevel on the outside of each bend. The pieces overlap, but unlike a
+/// single mitred outline none of them cross themselves, however short the segments or sharp the
+/// bends.
+pub fn thicken_polyline(points: &[Point2<f32>], half_width: f32) -> Vec<Vec<Point2<f32>>> {
+    let mut points: Vec<Vec2> = points.iter().map(|p| Vec2::new(p.x, p.y)).collect();
+    points.dedup();
+    if points.len() < 2 {
+        return Vec::new();
+    }
+
+    let normals: Vec<Vec2> = points
+        .windows(2)
+        .map(|segment| (segment[1] - segment[0]).normalize().perp())
+        .collect();
+    let to_outline = |outline: &[Vec2]| -> Vec<Point2<f32>> {
+        outline.iter().map(|p| Point2::new(p.x, p.y)).collect()
     };
-    let ret = mesh.into();
-    let elapsed = now.elapsed();
-    println!("into_polyanya_mesh total took: {:?}", elapsed);
-    ret
+
+    let mut outlines: Vec<Vec<Point2<f32>>> = points
+        .windows(2)
+        .zip(&normals)
+        .map(|(segment, normal)| {
+            let offset = *normal * half_width;
+            to_outline(&[
+                segment[0] + offset,
+                segment[1] + offset,
+                segment[1] - offset,
+                segment[0] - offset,
+            ])
+        })
+        .collect();
+    for i in 1..points.len() - 1 {
+        let (before, after) = (normals[i - 1], normals[i]);
+        let turn = before.perp_dot(after);
+        if turn.abs() < 1e-6 {
+            continue;
+        }
+        // The outside of a left turn is on the right.
+        let side = if turn > 0.0 { -half_width } else { half_width };
+        let point = points[i];
+        outlines.push(to_outline(&[
+            point,
+            point + before * side,
+            point + after * side,
+        ]));
+    }
+    outlines
+}
+
+/// Splits a polyline's segments into runs of connected points.
+pub fn polyline_chains(points: &[Point2<f32>], indices: &[[u32; 2]]) -> Vec<Vec<Point2<f32>>> {
+    let mut chains: Vec<Vec<Point2<f32>>> = Vec::new();
+    let mut last = None;
+    for &[a, b] in indices {
+        match chains.last_mut() {
+            Some(chain) if last == Some(a) => chain.push(points[b as usize]),
+            _ => chains.push(vec![points[a as usize], points[b as usize]]),
+        }
+        last = Some(b);
+    }
+    chains
+}
+
+/// Casts down onto `heightfield` to find the ground height at `point` on the XZ plane.
+pub fn heightfield_height_at(heightfield: &HeightField, point: Point2<f32>) -> Option<f32> {
+    let top = heightfield.root_aabb().maxs.y + 1.0;
+    let ray = Ray::new(
+        Point::new(point.x, top, point.y),
+        Vector::new(0.0, -1.0, 0.0),
+    );
+    heightfield
+        .cast_local_ray(&ray, f32::MAX, true)
+        .map(|toi| top - toi)
+}
+
+/// FNV-1a, so hashes are the same across builds and platforms.
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Points on a circle of `radius` around the origin, in counter-clockwise order.
+pub fn circle_points(radius: f32, segments: usize) -> Vec<Vec2> {
+    (0..segments)
+        .map(|i| {
+            let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+            Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
 }
 
-pub fn compute_navigable_faces<C: PolyanyaCollider>(
+/// The outline of a capsule around the segment from `a` to `b`, in counter-clockwise order, with
+/// `segments` points on each rounded end.
+pub fn capsule_points(a: Vec2, b: Vec2, radius: f32, segments: usize) -> Vec<Vec2> {
+    let direction = (b - a).try_normalize().unwrap_or(Vec2::X);
+    let start_angle = direction.y.atan2(direction.x) - std::f32::consts::FRAC_PI_2;
+    let half_circle = |centre: Vec2, start_angle: f32| {
+        (0..segments).map(move |i| {
+            let angle = start_angle + i as f32 / (segments - 1) as f32 * std::f32::consts::PI;
+            centre + Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+    };
+    half_circle(b, start_angle)
+        .chain(half_circle(a, start_angle + std::f32::consts::PI))
+        .collect()
+}
+
+/// The inner faces of the triangulation that can be walked on, with the area each one is in.
+pub fn compute_navigable_faces(
     cdt: &ConstrainedDelaunayTriangulation<Point2<f32>>,
-    colliders: (&C, &GlobalTransform),
-    // obstacle_edges: &HashSet<usize>,
-) -> HashSet<usize> {
-    let now = Instant::now();
-    // let mut obstacle_edges = HashSet::new();
-    // for edge in cdt.directed_edges() {
-    //     if edge.is_constraint_edge() {
-    //         // println!("{} is constraint edge", edge.index());
-    //         obstacle_edges.insert(edge.index());
-    //     }
-    // }
-
-    let mut navigable_faces = HashSet::new();
-
-    for face in cdt.inner_faces() {
-        let mut touches_constraint = 0;
-        for edge in face.adjacent_edges() {
-            // if obstacle_edges.contains(&edge.index()) {
-            //     touches_constraint = true;
-            //     break;
-            // }
-            if edge.is_constraint_edge() {
-                touches_constraint += 1;
+    regions: &NavMeshRegions,
+) -> HashMap<usize, NavMeshArea> {
+    let _span = info_span!("compute_navigable_faces").entered();
+
+    // Every region outline is cut into the triangulation as constraint edges, so the faces that
+    // can be reached from each other without crossing one are all in the same regions. Each of
+    // these groups is classified once, by the centre of its largest face.
+    let mut navigable_faces = HashMap::new();
+    let mut visited = vec![false; cdt.num_all_faces()];
+    for start in cdt.inner_faces() {
+        if visited[start.index()] {
+            continue;
+        }
+        visited[start.index()] = true;
+        let mut group = vec![start];
+        let mut next = 0;
+        while let Some(&face) = group.get(next) {
+            next += 1;
+            for edge in face.adjacent_edges() {
+                if edge.is_constraint_edge() {
+                    continue;
+                }
+                if let Some(neighbour) = edge.rev().face().as_inner() {
+                    if !visited[neighbour.index()] {
+                        visited[neighbour.index()] = true;
+                        group.push(neighbour);
+                    }
+                }
             }
-            // if outgoing_obstacle != incoming_obstacle {
-            //     if incoming_obstacle {
-            //         navigable_faces.insert(face.index());
-            //     }
-            //     continue 'face_loop;
-            // }
-        }
-
-        if touches_constraint < 2 {
-            navigable_faces.insert(face.index());
-        }
-
-        // for edge in edge.ccw_iter().skip(1) {
-        //     let outgoing_obstacle = obstacle_edges.contains(&edge.fix());
-        //     let incoming_obstacle = obstacle_edges.contains(&edge.sym().fix());
-        //     if outgoing_obstacle != incoming_obstacle {
-        //         if incoming_obstacle {
-        //             navigable_faces.insert(face.fix());
-        //         }
-        //         continue 'face_loop;
-        //     }
-        // }
-    }
-    let elapsed = now.elapsed();
-    // println!("compute_navigable_faces took: {:?}", elapsed);
+        }
+
+        let largest = group
+            .iter()
+            .max_by(|a, b| a.area().total_cmp(&b.area()))
+            .expect("groups start with a face");
+        let centre = largest.center();
+        if regions.is_navigable(centre) {
+            let area = regions.area_at(centre).unwrap_or_default();
+            navigable_faces.extend(group.iter().map(|face| (face.index(), area)));
+        }
+    }
     navigable_faces
 }
 
-// fn compute_navigable_faces(
-//     &self,
-//     triangulation: &Triangulation,
-//     obstacle_edges: &HashSet<usize>,
-//   ) -> HashSet<usize> {
-//     let mut navigable_faces = HashSet::new();
-//     'face_loop: for face in triangulation.triangles() {
-//       if let Some(edge) = face.adjacent_edge() {
-//         for edge in edge.ccw_iter().skip(1) {
-//           let outgoing_obstacle = obstacle_edges.contains(&edge.fix());
-//           let incoming_obstacle = obstacle_edges.contains(&edge.sym().fix());
-//           if outgoing_obstacle != incoming_obstacle {
-//             if incoming_obstacle {
-//               navigable_faces.insert(face.fix());
-//             }
-//             continue 'face_loop;
-//           }
-//         }
-//       }
-//     }
-//     navigable_faces
-//   }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{add_collider_to_navmesh, bounding_cdt, colliders::Footprint, testing};
+
+    fn crosses_itself(outline: &[Point2<f32>]) -> bool {
+        let edge = |i: usize| {
+            let a = outline[i];
+            let b = outline[(i + 1) % outline.len()];
+            (Vec2::new(a.x, a.y), Vec2::new(b.x, b.y))
+        };
+        let side = |(a, b): (Vec2, Vec2), point: Vec2| (b - a).perp_dot(point - a);
+        (0..outline.len()).any(|i| {
+            (i + 2..outline.len())
+                .filter(|&j| (j + 1) % outline.len() != i)
+                .any(|j| {
+                    let (first, second) = (edge(i), edge(j));
+                    side(first, second.0) * side(first, second.1) < 0.0
+                        && side(second, first.0) * side(second, first.1) < 0.0
+                })
+        })
+    }
+
+    fn blocked(regions: &NavMeshRegions, (x, y): (f32, f32)) -> bool {
+        !regions.is_navigable(Point2::new(x, y))
+    }
+
+    #[test]
+    fn convex_hull_drops_inner_and_collinear_points() {
+        let points = [
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+            (1.0, 1.0),
+            (1.0, 1.0),
+            (0.0, 1.0),
+        ]
+        .map(|(x, y)| Point2::new(x, y));
+        let expected =
+            [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)].map(|(x, y)| Point2::new(x, y));
+        assert_eq!(convex_hull_2d(points.to_vec()), expected);
+    }
+
+    #[test]
+    fn sharp_corners_dont_cross_themselves() {
+        let points = [
+            Point2::new(0.0, 0.0),
+            Point2::new(1.0, 0.0),
+            Point2::new(0.0, 0.2),
+        ];
+        let outlines = thicken_polyline(&points, 0.5);
+        assert!(outlines.iter().all(|outline| !crosses_itself(outline)));
+
+        let regions = NavMeshRegions {
+            obstacles: outlines.clone(),
+            ..Default::default()
+        };
+        for point in [(0.5, 0.3), (0.9, -0.4), (1.02, 0.0), (0.2, 0.6)] {
+            assert!(blocked(&regions, point), "{:?} should be blocked", point);
+        }
+        for point in [(0.5, 1.5), (2.0, 0.0), (0.5, -1.0)] {
+            assert!(!blocked(&regions, point), "{:?} should be open", point);
+        }
+
+        let mut cdt = bounding_cdt((10.0, 10.0));
+        let footprint = Footprint::Open(points.to_vec());
+        assert_eq!(
+            add_collider_to_navmesh(&footprint, 0.5, (0.0, 0.0), &mut cdt),
+            outlines
+        );
+    }
+
+    #[test]
+    fn joined_walls_can_overlap() {
+        let walls = [
+            vec![Point2::new(-3.0, 0.0), Point2::new(3.0, 0.0)],
+            vec![Point2::new(0.0, 0.0), Point2::new(0.0, 3.0)],
+        ];
+        let mut cdt = bounding_cdt((10.0, 10.0));
+        let mut regions = NavMeshRegions::default();
+        for wall in walls {
+            let footprint = Footprint::Open(wall);
+            let kind = AffectorKind::Obstacle;
+            for outline in testing::cut(&mut cdt, &mut regions, kind, &footprint, 0.25) {
+                assert!(!crosses_itself(&outline));
+            }
+        }
+
+        for point in [(-2.0, 0.1), (0.1, 0.1), (-0.1, 2.0), (2.0, -0.2)] {
+            assert!(blocked(&regions, point), "{:?} should be blocked", point);
+        }
+        for point in [(-1.0, 1.0), (1.0, 1.0), (0.0, -1.0)] {
+            assert!(!blocked(&regions, point), "{:?} should be open", point);
+        }
+        // Faces never straddle an outline, so their centres classify them correctly.
+        for face in cdt.inner_faces() {
+            let centre = face.center();
+            let in_wall = (centre.x.abs() < 3.0 && centre.y.abs() < 0.25)
+                || (centre.x.abs() < 0.25 && centre.y > -0.25 && centre.y < 3.0);
+            assert_eq!(regions.is_navigable(centre), !in_wall, "{:?}", centre);
+        }
+    }
+}
@@ -0,0 +1,415 @@
+//! Checks for mistakes in a navmesh that would otherwise only show up as failed path queries.
+
+use std::fmt;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::PolyNavMesh;
+
+/// Polygons with less area than this are degenerate.
+const MIN_AREA: f32 = 1e-6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MeshProblem {
+    /// Two vertices at the same position.
+    DuplicateVertex {
+        first: usize,
+        second: usize,
+    },
+    /// A polygon uses a vertex that doesn't exist.
+    MissingVertex {
+        polygon: usize,
+        vertex: usize,
+    },
+    /// A polygon with fewer than three vertices or no area.
+    DegeneratePolygon {
+        polygon: usize,
+    },
+    NonConvexPolygon {
+        polygon: usize,
+    },
+    /// A vertex lists a polygon that doesn't exist or doesn't use it, or a polygon uses a vertex
+    /// that doesn't list it.
+    BrokenNeighbour {
+        vertex: usize,
+        polygon: usize,
+    },
+    /// Connected polygons with less surface area than the `min_island_area` they were checked
+    /// with, which should have been left out. Larger islands, such as rooms walled off from each
+    /// other, are fine.
+    Island {
+        polygons: Vec<usize>,
+        area: f32,
+    },
+}
+
+impl fmt::Display for MeshProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshProblem::DuplicateVertex { first, second } => {
+                write!(
+                    f,
+                    "vertices {} and {} are at the same position",
+                    first, second
+                )
+            }
+            MeshProblem::MissingVertex { polygon, vertex } => {
+                write!(f, "polygon {} uses missing vertex {}", polygon, vertex)
+            }
+            MeshProblem::DegeneratePolygon { polygon } => {
+                write!(f, "polygon {} is degenerate", polygon)
+            }
+            MeshProblem::NonConvexPolygon { polygon } => {
+                write!(f, "polygon {} is not convex", polygon)
+            }
+            MeshProblem::BrokenNeighbour { vertex, polygon } => write!(
+                f,
+                "vertex {} and polygon {} disagree about being neighbours",
+                vertex, polygon
+            ),
+            MeshProblem::Island { polygons, area } => write!(
+                f,
+                "island of {} polygons starting with polygon {} only covers {}",
+                polygons.len(),
+                polygons[0],
+                area
+            ),
+        }
+    }
+}
+
+/// The problems found in each layer of a [`PolyNavMesh`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    /// One per layer, lowest first.
+    pub layers: Vec<Vec<MeshProblem>>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.layers.iter().all(Vec::is_empty)
+    }
+
+    /// Every problem with the layer it's in.
+    pub fn problems(&self) -> impl Iterator<Item = (usize, &MeshProblem)> {
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(layer, problems)| problems.iter().map(move |problem| (layer, problem)))
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_valid() {
+            return write!(f, "no problems found");
+        }
+        for (layer, problem) in self.problems() {
+            writeln!(f, "layer {}: {}", layer, problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl PolyNavMesh {
+    /// Checks the polyanya mesh of every layer's [`PathMesh`](bevy_pathmesh::PathMesh) with
+    /// [`validate_mesh`], as generated, baked or imported.
+    pub fn validate(&self) -> ValidationReport {
+        let _span = info_span!("validate_navmesh").entered();
+        ValidationReport {
+            layers: self
+                .meshes
+                .iter()
+                .map(|mesh| validate_mesh(mesh, self.min_island_area))
+                .collect(),
+        }
+    }
+}
+
+/// Checks that `mesh` is well formed and has no islands smaller than `min_island_area`.
+pub fn validate_mesh(mesh: &polyanya::Mesh, min_island_area: f32) -> Vec<MeshProblem> {
+    let mut problems = Vec::new();
+
+    let mut positions: HashMap<[u32; 2], usize> = HashMap::new();
+    for (index, vertex) in mesh.vertices.iter().enumerate() {
+        // Adding zero turns -0.0 into 0.0 so they compare equal.
+        let key = [vertex.coords.x + 0.0, vertex.coords.y + 0.0].map(f32::to_bits);
+        if let Some(&first) = positions.get(&key) {
+            problems.push(MeshProblem::DuplicateVertex {
+                first,
+                second: index,
+            });
+        } else {
+            positions.insert(key, index);
+        }
+    }
+
+    let mut valid_polygons = Vec::with_capacity(mesh.polygons.len());
+    for (index, polygon) in mesh.polygons.iter().enumerate() {
+        let missing: Vec<_> = polygon
+            .vertices
+            .iter()
+            .copied()
+            .filter(|&vertex| vertex >= mesh.vertices.len())
+            .collect();
+        for &vertex in &missing {
+            problems.push(MeshProblem::MissingVertex {
+                polygon: index,
+                vertex,
+            });
+        }
+        if !missing.is_empty() {
+            continue;
+        }
+        valid_polygons.push(index);
+
+        let points: Vec<Vec2> = polygon
+            .vertices
+            .iter()
+            .map(|&vertex| mesh.vertices[vertex].coords)
+            .collect();
+        if points.len() < 3 || signed_area(&points).abs() < MIN_AREA {
+            problems.push(MeshProblem::DegeneratePolygon { polygon: index });
+        } else if !is_convex(&points) {
+            problems.push(MeshProblem::NonConvexPolygon { polygon: index });
+        }
+
+        for &vertex in &polygon.vertices {
+            if !mesh.vertices[vertex].polygons.contains(&(index as isize)) {
+                problems.push(MeshProblem::BrokenNeighbour {
+                    vertex,
+                    polygon: index,
+                });
+            }
+        }
+    }
+
+    for (index, vertex) in mesh.vertices.iter().enumerate() {
+        // Negative polygons mark the outside of the mesh.
+        for &polygon in vertex.polygons.iter().filter(|&&polygon| polygon >= 0) {
+            let polygon = polygon as usize;
+            let uses_vertex = mesh
+                .polygons
+                .get(polygon)
+                .map_or(false, |polygon| polygon.vertices.contains(&index));
+            if !uses_vertex {
+                problems.push(MeshProblem::BrokenNeighbour {
+                    vertex: index,
+                    polygon,
+                });
+            }
+        }
+    }
+
+    for polygons in polygon_islands(mesh, &valid_polygons) {
+        let area: f32 = polygons
+            .iter()
+            .map(|&polygon| {
+                let points: Vec<Vec2> = mesh.polygons[polygon]
+                    .vertices
+                    .iter()
+                    .map(|&vertex| mesh.vertices[vertex].coords)
+                    .collect();
+                signed_area(&points).abs()
+            })
+            .sum();
+        if area < min_island_area {
+            problems.push(MeshProblem::Island { polygons, area });
+        }
+    }
+
+    problems
+}
+
+/// Groups the polygons into sets connected through shared edges.
+pub(crate) fn polygon_islands(mesh: &polyanya::Mesh, polygons: &[usize]) -> Vec<Vec<usize>> {
+    let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for &polygon in polygons {
+        let vertices = &mesh.polygons[polygon].vertices;
+        for (i, &a) in vertices.iter().enumerate() {
+            let b = vertices[(i + 1) % vertices.len()];
+            edges.entry((a.min(b), a.max(b))).or_default().push(polygon);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut islands = Vec::new();
+    for &start in polygons {
+        if !visited.insert(start) {
+            continue;
+        }
+        let mut island = vec![start];
+        let mut i = 0;
+        while let Some(&polygon) = island.get(i) {
+            i += 1;
+            let vertices = &mesh.polygons[polygon].vertices;
+            for (j, &a) in vertices.iter().enumerate() {
+                let b = vertices[(j + 1) % vertices.len()];
+                for &neighbour in &edges[&(a.min(b), a.max(b))] {
+                    if visited.insert(neighbour) {
+                        island.push(neighbour);
+                    }
+                }
+            }
+        }
+        island.sort_unstable();
+        islands.push(island);
+    }
+    islands
+}
+
+fn signed_area(points: &[Vec2]) -> f32 {
+    let mut area = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        area += a.perp_dot(b);
+    }
+    area * 0.5
+}
+
+/// Whether every corner turns the same way, allowing for collinear points.
+fn is_convex(points: &[Vec2]) -> bool {
+    let mut turn = 0.0;
+    for (i, &a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        let c = points[(i + 2) % points.len()];
+        let cross = (b - a).perp_dot(c - b);
+        if cross.abs() < MIN_AREA {
+            continue;
+        }
+        if cross * turn < 0.0 {
+            return false;
+        }
+        turn = cross;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh(vertices: &[[f32; 2]], triangles: &[[usize; 3]]) -> polyanya::Mesh {
+        polyanya::Trimesh {
+            vertices: vertices.iter().copied().map(Vec2::from).collect(),
+            triangles: triangles.to_vec(),
+        }
+        .into()
+    }
+
+    /// Two counter-clockwise triangles making up a unit square.
+    fn square() -> polyanya::Mesh {
+        mesh(
+            &[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            &[[0, 1, 2], [0, 2, 3]],
+        )
+    }
+
+    #[test]
+    fn valid_mesh_has_no_problems() {
+        assert_eq!(validate_mesh(&square(), 0.5), vec![]);
+    }
+
+    #[test]
+    fn duplicate_vertex() {
+        let mesh = mesh(
+            &[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [-0.0, 0.0]],
+            &[[0, 1, 2], [0, 2, 3]],
+        );
+        assert!(
+            validate_mesh(&mesh, 0.0).contains(&MeshProblem::DuplicateVertex {
+                first: 0,
+                second: 4
+            })
+        );
+    }
+
+    #[test]
+    fn missing_vertex() {
+        let mut mesh = square();
+        mesh.polygons[1].vertices.push(7);
+        assert!(
+            validate_mesh(&mesh, 0.0).contains(&MeshProblem::MissingVertex {
+                polygon: 1,
+                vertex: 7
+            })
+        );
+    }
+
+    #[test]
+    fn degenerate_polygon() {
+        let mesh = mesh(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0]], &[[0, 1, 2]]);
+        assert!(validate_mesh(&mesh, 0.0).contains(&MeshProblem::DegeneratePolygon { polygon: 0 }));
+    }
+
+    #[test]
+    fn non_convex_polygon() {
+        let mut mesh = mesh(
+            &[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [0.5, 0.5]],
+            &[[0, 1, 2], [0, 2, 3]],
+        );
+        // A dart, dented in at the middle of the square.
+        mesh.polygons[0].vertices = vec![0, 1, 4, 2, 3];
+        assert!(validate_mesh(&mesh, 0.0).contains(&MeshProblem::NonConvexPolygon { polygon: 0 }));
+    }
+
+    #[test]
+    fn broken_neighbour() {
+        let mut mesh = square();
+        mesh.vertices[3].polygons.retain(|&polygon| polygon != 1);
+        mesh.vertices[1].polygons.push(1);
+        let problems = validate_mesh(&mesh, 0.0);
+        // Polygon 1 uses vertex 3, which doesn't list it.
+        assert!(problems.contains(&MeshProblem::BrokenNeighbour {
+            vertex: 3,
+            polygon: 1
+        }));
+        // Vertex 1 lists polygon 1, which doesn't use it.
+        assert!(problems.contains(&MeshProblem::BrokenNeighbour {
+            vertex: 1,
+            polygon: 1
+        }));
+    }
+
+    #[test]
+    fn only_small_islands_are_problems() {
+        let mesh = mesh(
+            &[
+                [0.0, 0.0],
+                [1.0, 0.0],
+                [1.0, 1.0],
+                [0.0, 1.0],
+                [2.0, 0.0],
+                [2.1, 0.0],
+                [2.1, 0.1],
+                [2.0, 0.1],
+                [3.0, 0.0],
+                [4.0, 0.0],
+                [4.0, 1.0],
+                [3.0, 1.0],
+            ],
+            &[
+                [0, 1, 2],
+                [0, 2, 3],
+                [4, 5, 6],
+                [4, 6, 7],
+                [8, 9, 10],
+                [8, 10, 11],
+            ],
+        );
+        assert_eq!(validate_mesh(&mesh, 0.0), vec![]);
+        let problems = validate_mesh(&mesh, 0.5);
+        assert!(
+            matches!(
+                problems.as_slice(),
+                [MeshProblem::Island { polygons, area }]
+                    if polygons == &[2, 3] && (area - 0.01).abs() < 1e-4
+            ),
+            "{:?}",
+            problems
+        );
+    }
+}